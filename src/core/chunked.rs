@@ -0,0 +1,181 @@
+use std::fmt::{self, Display, Formatter};
+
+/// Longest chunk-size or trailer line accepted before the body is rejected.
+const MAX_LINE_BYTES: usize = 4096;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChunkedError {
+  InvalidChunkSize,
+  MissingChunkTerminator,
+  LineTooLong,
+  /// The trailer section has more fields or bytes than the decoder allows.
+  TrailersTooLarge,
+}
+
+impl Display for ChunkedError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    let text = match self {
+      ChunkedError::InvalidChunkSize => "invalid chunk size",
+      ChunkedError::MissingChunkTerminator => "chunk data not followed by CRLF",
+      ChunkedError::LineTooLong => "chunk line too long",
+      ChunkedError::TrailersTooLarge => "trailer section too large",
+    };
+    write!(f, "{}", text)
+  }
+}
+
+impl std::error::Error for ChunkedError {}
+
+enum State {
+  Size,
+  Data(usize),
+  DataEnd,
+  Trailers,
+  Done,
+}
+
+/// Incremental decoder for `Transfer-Encoding: chunked` bodies (RFC 9112, section 7.1).
+///
+/// Bytes are pushed in as they arrive; `feed` reports how many it consumed, so whatever
+/// follows the last chunk is left for the next message on the connection.
+pub struct ChunkedDecoder {
+  state: State,
+  line: Vec<u8>,
+  body: Vec<u8>,
+  trailers: Vec<(String, String)>,
+  trailer_bytes: usize,
+  max_trailer_fields: usize,
+  max_trailer_bytes: usize,
+}
+
+impl Default for ChunkedDecoder {
+  fn default() -> Self {
+    ChunkedDecoder {
+      state: State::Size,
+      line: Vec::new(),
+      body: Vec::new(),
+      trailers: Vec::new(),
+      trailer_bytes: 0,
+      max_trailer_fields: usize::MAX,
+      max_trailer_bytes: usize::MAX,
+    }
+  }
+}
+
+impl ChunkedDecoder {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// A decoder that fails with `TrailersTooLarge` once the trailer section holds more than
+  /// `max_fields` fields or `max_bytes` bytes, the same budget a request's header section has.
+  pub fn with_trailer_limits(max_fields: usize, max_bytes: usize) -> Self {
+    ChunkedDecoder {
+      max_trailer_fields: max_fields,
+      max_trailer_bytes: max_bytes,
+      ..Self::default()
+    }
+  }
+
+  /// True once the final zero-size chunk and the trailer section have been read.
+  pub fn is_done(&self) -> bool {
+    matches!(self.state, State::Done)
+  }
+
+  /// The payload decoded so far.
+  pub fn body(&self) -> &[u8] {
+    &self.body
+  }
+
+  /// Consumes the decoder, returning the payload and any trailer fields.
+  pub fn into_parts(self) -> (Vec<u8>, Vec<(String, String)>) {
+    (self.body, self.trailers)
+  }
+
+  /// Decodes as much of `input` as possible and returns the number of bytes consumed.
+  pub fn feed(&mut self, input: &[u8]) -> Result<usize, ChunkedError> {
+    let mut pos = 0;
+    while pos < input.len() {
+      match self.state {
+        State::Done => break,
+        State::Data(remaining) => {
+          let take = remaining.min(input.len() - pos);
+          self.body.extend_from_slice(&input[pos..pos + take]);
+          pos += take;
+          self.state = if take == remaining {
+            State::DataEnd
+          } else {
+            State::Data(remaining - take)
+          };
+        }
+        State::Size | State::DataEnd | State::Trailers => match input[pos..].iter().position(|&b| b == b'\n') {
+          Some(i) => {
+            self.line.extend_from_slice(&input[pos..pos + i]);
+            pos += i + 1;
+            let line = std::mem::take(&mut self.line);
+            self.finish_line(&line)?;
+          }
+          None => {
+            self.line.extend_from_slice(&input[pos..]);
+            pos = input.len();
+          }
+        },
+      }
+      if self.line.len() > MAX_LINE_BYTES {
+        return Err(ChunkedError::LineTooLong);
+      }
+    }
+    Ok(pos)
+  }
+
+  fn finish_line(&mut self, line: &[u8]) -> Result<(), ChunkedError> {
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    match self.state {
+      State::Size => {
+        // Chunk extensions (";name=value") carry nothing we act on, so they are skipped.
+        let text = std::str::from_utf8(line).map_err(|_| ChunkedError::InvalidChunkSize)?;
+        let size_str = text.split(';').next().unwrap_or("").trim();
+        if size_str.is_empty() || !size_str.bytes().all(|b| b.is_ascii_hexdigit()) {
+          return Err(ChunkedError::InvalidChunkSize);
+        }
+        let size = usize::from_str_radix(size_str, 16).map_err(|_| ChunkedError::InvalidChunkSize)?;
+        self.state = if size == 0 { State::Trailers } else { State::Data(size) };
+      }
+      State::DataEnd => {
+        if !line.is_empty() {
+          return Err(ChunkedError::MissingChunkTerminator);
+        }
+        self.state = State::Size;
+      }
+      State::Trailers => {
+        if line.is_empty() {
+          self.state = State::Done;
+          return Ok(());
+        }
+        self.trailer_bytes += line.len() + 2;
+        if self.trailer_bytes > self.max_trailer_bytes {
+          return Err(ChunkedError::TrailersTooLarge);
+        }
+        if let Some((name, value)) = String::from_utf8_lossy(line).split_once(':') {
+          if self.trailers.len() >= self.max_trailer_fields {
+            return Err(ChunkedError::TrailersTooLarge);
+          }
+          self.trailers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+      }
+      State::Data(_) | State::Done => {}
+    }
+    Ok(())
+  }
+}
+
+/// The zero-size chunk and empty trailer section that end a chunked body.
+pub const LAST_CHUNK: &[u8] = b"0\r\n\r\n";
+
+/// Frames `data` as a single chunk (size line, data, CRLF).
+pub fn encode_chunk(data: &[u8]) -> Vec<u8> {
+  let mut out = format!("{:x}\r\n", data.len()).into_bytes();
+  out.extend_from_slice(data);
+  out.extend_from_slice(b"\r\n");
+  out
+}
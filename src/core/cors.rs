@@ -1,3 +1,4 @@
+use crate::core::extensions::Extensions;
 use crate::core::response::Response;
 use crate::core::status_code::StatusCode;
 
@@ -92,9 +93,66 @@ impl CorsPolicy {
 
   pub fn preflight_response(&self) -> Response {
     Response {
-      status: StatusCode::NoContent.to_string(),
+      status: StatusCode::NoContent,
       content_type: "text/plain".to_string(),
       content: Vec::new(),
+      headers: Vec::new(),
+      stream: None,
+      extensions: Extensions::new(),
     }
   }
 }
+
+/// Marks a response a CORS policy has already handled, even when the origin was refused and
+/// no header was added, so an outer policy (the server's around a group's) leaves it alone.
+#[cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+pub(crate) struct CorsApplied;
+
+/// Whether a response already carries CORS headers, set by the handler itself.
+#[cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+pub(crate) fn has_cors_headers(response: &Response) -> bool {
+  response
+    .headers
+    .iter()
+    .any(|(k, _)| k.len() > 15 && k.as_bytes()[..15].eq_ignore_ascii_case(b"access-control-"))
+}
+
+/// Adds the policy's headers to responses no other policy has handled and that have no CORS
+/// headers yet, and answers `OPTIONS` requests for paths without any route as preflight
+/// requests.
+///
+/// Servers install their policy (`set_cors`) as the outermost global middleware; route groups
+/// install theirs (`Router::cors`) around the group's routes, so a group's decision, including
+/// refusing an origin, is final.
+#[cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+#[async_trait::async_trait]
+impl crate::core::middleware::Middleware for CorsPolicy {
+  async fn call(&self, request: &mut crate::Request, next: crate::core::middleware::Next<'_>) -> Response {
+    let origin = request.origin().map(str::to_string);
+    let mut response = if request.method == crate::Rt::OPTIONS && !request.is_routed() {
+      self.preflight_response()
+    } else {
+      next.run(request).await
+    };
+    if response.extensions.get::<CorsApplied>().is_none() && !has_cors_headers(&response) {
+      response.headers.extend(self.header_lines(origin.as_deref()));
+    }
+    response.extensions.insert(CorsApplied);
+    response
+  }
+}
@@ -0,0 +1,113 @@
+#![cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+
+use crate::core::handler::Handler;
+use crate::core::status_code::StatusCode;
+use crate::{Request, Response};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// Builds the response for an error the server produces itself, from the request being
+/// answered (only its header fields when the request could not be parsed) and the status.
+pub type ErrorRenderer = Arc<dyn Fn(&Request, StatusCode) -> Response + Send + Sync>;
+
+/// What a server answers when no route does: an optional fallback handler for unmatched
+/// requests, and renderers for the error responses the server builds on its own (404, 405,
+/// and the 400/408/413/414/431/505 refusals of the request parser).
+///
+/// Responses returned by handlers are sent as they are.
+#[derive(Clone, Default)]
+pub struct ErrorPages {
+  fallback: Option<Arc<dyn Handler>>,
+  renderers: HashMap<StatusCode, ErrorRenderer>,
+  default_renderer: Option<ErrorRenderer>,
+}
+
+impl ErrorPages {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Handles requests that match no route and no static file, instead of the plain 404.
+  pub fn set_fallback(&mut self, handler: Arc<dyn Handler>) {
+    self.fallback = Some(handler);
+  }
+
+  /// Renders the server's own error responses with `status`.
+  pub fn set_renderer(&mut self, status: StatusCode, renderer: ErrorRenderer) {
+    self.renderers.insert(status, renderer);
+  }
+
+  /// Renders the server's own 4xx and 5xx responses that have no renderer for their status.
+  pub fn set_default_renderer(&mut self, renderer: ErrorRenderer) {
+    self.default_renderer = Some(renderer);
+  }
+
+  /// Answers a request nothing else matched: the fallback handler's response, or a
+  /// rendered 404.
+  pub async fn not_found(&self, request: &Request) -> Response {
+    match &self.fallback {
+      Some(handler) => handler.handle(request).await,
+      None => self.render(request, Response::new()),
+    }
+  }
+
+  /// Swaps the body of an error response for the rendered one. The status and the header
+  /// fields of `response`, such as `Allow` on a 405, are kept.
+  pub fn render(&self, request: &Request, response: Response) -> Response {
+    let renderer = match self.renderers.get(&response.status) {
+      Some(renderer) => renderer,
+      None => match &self.default_renderer {
+        Some(renderer) if response.status.as_u16() >= 400 => renderer,
+        _ => return response,
+      },
+    };
+    let mut rendered = renderer(request, response.status);
+    rendered.status = response.status;
+    rendered.headers.extend(response.headers);
+    rendered
+  }
+}
+
+/// Renders a problem details document (RFC 9457) such as
+/// `{"type":"about:blank","title":"Not Found","status":404,"instance":"/missing"}`,
+/// served as `application/problem+json`.
+pub fn problem_json(request: &Request, status: StatusCode) -> Response {
+  let mut body = format!(
+    "{{\"type\":\"about:blank\",\"title\":{},\"status\":{}",
+    json_string(status.reason_phrase()),
+    status.as_u16()
+  );
+  if !request.path.is_empty() {
+    let _ = write!(body, ",\"instance\":{}", json_string(&request.path));
+  }
+  body.push('}');
+  Response::builder()
+    .content_type("application/problem+json")
+    .body(body.into_bytes())
+}
+
+fn json_string(value: &str) -> String {
+  let mut out = String::with_capacity(value.len() + 2);
+  out.push('"');
+  for c in value.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      c if (c as u32) < 0x20 => {
+        let _ = write!(out, "\\u{:04x}", c as u32);
+      }
+      c => out.push(c),
+    }
+  }
+  out.push('"');
+  out
+}
@@ -0,0 +1,72 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+
+/// Typed values attached to a single request or response, at most one per type.
+///
+/// Middleware uses `Request::extensions` to hand data such as the authenticated user or a
+/// request id to the handler, and handlers use `Response::extensions` to pass data back out
+/// to the middleware around them. Extensions are never sent to the client.
+///
+/// Defining a small type per value, e.g. `struct RequestId(String)`, keeps unrelated
+/// components from overwriting each other's entries.
+#[derive(Default)]
+pub struct Extensions {
+  values: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl Extensions {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Stores `value`, returning the value of the same type it replaces.
+  pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+    self
+      .values
+      .insert(TypeId::of::<T>(), Box::new(value))
+      .and_then(|old| old.downcast::<T>().ok())
+      .map(|old| *old)
+  }
+
+  /// The stored value of type `T`, if any.
+  pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+    self
+      .values
+      .get(&TypeId::of::<T>())
+      .and_then(|value| value.downcast_ref::<T>())
+  }
+
+  /// Mutable access to the stored value of type `T`, if any.
+  pub fn get_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+    self
+      .values
+      .get_mut(&TypeId::of::<T>())
+      .and_then(|value| value.downcast_mut::<T>())
+  }
+
+  /// Takes the stored value of type `T` out.
+  pub fn remove<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+    self
+      .values
+      .remove(&TypeId::of::<T>())
+      .and_then(|value| value.downcast::<T>().ok())
+      .map(|value| *value)
+  }
+
+  pub fn len(&self) -> usize {
+    self.values.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.values.is_empty()
+  }
+}
+
+impl Debug for Extensions {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Extensions")
+      .field("values", &self.values.len())
+      .finish()
+  }
+}
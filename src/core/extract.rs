@@ -0,0 +1,306 @@
+#![cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+
+use crate::core::request::ParamError;
+use crate::core::response::IntoResponse;
+use crate::core::status_code::StatusCode;
+use crate::{Request, Response};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// A value built from the request before a handler runs, so the handler can take it as an
+/// argument, e.g. `fn show(Path(id): Path<u64>) -> Response`. See `extract_handler!`.
+pub trait FromRequest: Sized {
+  fn from_request(request: &Request) -> Result<Self, Rejection>;
+}
+
+/// Why an extractor could not build its value. It is answered with `status` and `message`
+/// as plain text instead of running the handler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rejection {
+  pub status: StatusCode,
+  pub message: String,
+}
+
+impl Rejection {
+  pub fn new<M: Into<String>>(status: StatusCode, message: M) -> Self {
+    Rejection {
+      status,
+      message: message.into(),
+    }
+  }
+}
+
+impl Display for Rejection {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}: {}", self.status, self.message)
+  }
+}
+
+impl std::error::Error for Rejection {}
+
+impl From<Rejection> for Response {
+  fn from(rejection: Rejection) -> Self {
+    Response::builder().status(rejection.status).text(rejection.message)
+  }
+}
+
+impl IntoResponse for Rejection {
+  fn into_response(self) -> Response {
+    self.into()
+  }
+}
+
+/// The path parameters captured by the matched route. A single value works for routes with
+/// one parameter, `Path<u64>`; tuples take several in pattern order, `Path<(String, u32)>`.
+/// A value that does not parse is rejected with 400 Bad Request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path<T>(pub T);
+
+/// Types `Path` can build from the route's `(name, value)` captures, in pattern order.
+pub trait FromPath: Sized {
+  fn from_path(values: &[(&str, &str)]) -> Result<Self, Rejection>;
+}
+
+impl<T: FromPath> FromRequest for Path<T> {
+  fn from_request(request: &Request) -> Result<Self, Rejection> {
+    T::from_path(&request.path_values()).map(Path)
+  }
+}
+
+fn parse_path_value<T>((name, value): (&str, &str)) -> Result<T, Rejection>
+where
+  T: FromStr,
+  T::Err: Display,
+{
+  value.parse().map_err(|err: T::Err| {
+    let err = ParamError::Invalid {
+      name: name.to_string(),
+      value: value.to_string(),
+      reason: err.to_string(),
+    };
+    Rejection::new(StatusCode::BadRequest, err.to_string())
+  })
+}
+
+// A route with a different number of parameters than the handler asks for is a programming
+// error, not a bad request.
+fn path_arity(values: &[(&str, &str)], expected: usize) -> Result<(), Rejection> {
+  if values.len() == expected {
+    return Ok(());
+  }
+  Err(Rejection::new(
+    StatusCode::InternalServerError,
+    format!(
+      "expected {} path parameter(s), the route captured {}",
+      expected,
+      values.len()
+    ),
+  ))
+}
+
+macro_rules! impl_from_path_value {
+  ($($ty:ty),*) => {
+    $(
+      impl FromPath for $ty {
+        fn from_path(values: &[(&str, &str)]) -> Result<Self, Rejection> {
+          path_arity(values, 1)?;
+          parse_path_value(values[0])
+        }
+      }
+    )*
+  };
+}
+
+impl_from_path_value!(
+  String, bool, char, u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64
+);
+
+macro_rules! impl_from_path_tuple {
+  ($count:expr; $($ty:ident => $index:tt),+) => {
+    impl<$($ty),+> FromPath for ($($ty,)+)
+    where
+      $($ty: FromStr, $ty::Err: Display,)+
+    {
+      fn from_path(values: &[(&str, &str)]) -> Result<Self, Rejection> {
+        path_arity(values, $count)?;
+        Ok(($(parse_path_value::<$ty>(values[$index])?,)+))
+      }
+    }
+  };
+}
+
+impl_from_path_tuple!(1; A => 0);
+impl_from_path_tuple!(2; A => 0, B => 1);
+impl_from_path_tuple!(3; A => 0, B => 1, C => 2);
+impl_from_path_tuple!(4; A => 0, B => 1, C => 2, D => 3);
+
+/// A copy of the server's shared state of type `T`, see `Server::with_state`. Missing state
+/// is a server misconfiguration and answers 500 Internal Server Error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct State<T>(pub T);
+
+impl<T: Clone + Send + Sync + 'static> FromRequest for State<T> {
+  fn from_request(request: &Request) -> Result<Self, Rejection> {
+    match request.state::<T>() {
+      Some(state) => Ok(State(state.clone())),
+      None => Err(Rejection::new(
+        StatusCode::InternalServerError,
+        format!("no state of type `{}` is registered", std::any::type_name::<T>()),
+      )),
+    }
+  }
+}
+
+/// The query string deserialized into `T`, e.g. a struct with one field per parameter.
+/// Numbers and booleans are parsed from their text; absent `Option` fields are `None`.
+/// A missing or unparsable parameter is rejected with 400 Bad Request.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query<T>(pub T);
+
+#[cfg(feature = "json")]
+impl<T: serde::de::DeserializeOwned> FromRequest for Query<T> {
+  fn from_request(request: &Request) -> Result<Self, Rejection> {
+    let pairs = request.params.iter().map(|(k, v)| (k.as_str(), query::Value(v)));
+    T::deserialize(query::Map::new(pairs))
+      .map(Query)
+      .map_err(|err| Rejection::new(StatusCode::BadRequest, format!("invalid query string: {}", err)))
+  }
+}
+
+/// The body deserialized from JSON. A request without an `application/json` (or `+json`)
+/// content type is rejected with 415 Unsupported Media Type, malformed JSON with 400 Bad
+/// Request and JSON that does not fit `T` with 422 Unprocessable Entity.
+///
+/// Returned from a handler, it answers with `T` serialized as JSON.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Json<T>(pub T);
+
+#[cfg(feature = "json")]
+impl<T: serde::de::DeserializeOwned> FromRequest for Json<T> {
+  fn from_request(request: &Request) -> Result<Self, Rejection> {
+    let content_type = request
+      .headers
+      .iter()
+      .find(|(k, _)| k.eq_ignore_ascii_case("content-type"))
+      .map(|(_, v)| v.split(';').next().unwrap_or("").trim().to_ascii_lowercase());
+    let is_json = content_type
+      .as_deref()
+      .is_some_and(|media| media == "application/json" || media.ends_with("+json"));
+    if !is_json {
+      return Err(Rejection::new(
+        StatusCode::UnsupportedMediaType,
+        "expected a request with `Content-Type: application/json`",
+      ));
+    }
+    serde_json::from_slice(&request.body).map(Json).map_err(|err| {
+      let status = match err.classify() {
+        serde_json::error::Category::Data => StatusCode::UnprocessableEntity,
+        _ => StatusCode::BadRequest,
+      };
+      Rejection::new(status, format!("invalid JSON body: {}", err))
+    })
+  }
+}
+
+#[cfg(feature = "json")]
+impl<T: serde::Serialize> IntoResponse for Json<T> {
+  fn into_response(self) -> Response {
+    Response::builder().json(&self.0)
+  }
+}
+
+// Query parameters are plain strings; serde's own string deserializer would refuse to
+// read them as numbers or booleans, so each value parses itself into what is asked for.
+#[cfg(feature = "json")]
+mod query {
+  use serde::de::value::{Error, MapDeserializer};
+  use serde::de::{self, Error as _, IntoDeserializer, Visitor};
+
+  pub(super) type Map<'a, I> = MapDeserializer<'a, I, Error>;
+
+  pub(super) struct Value<'a>(pub &'a str);
+
+  impl Value<'_> {
+    fn parse<T>(&self, kind: &str) -> Result<T, Error>
+    where
+      T: std::str::FromStr,
+      T::Err: std::fmt::Display,
+    {
+      self
+        .0
+        .parse()
+        .map_err(|err| Error::custom(format!("expected {}, found {:?}: {}", kind, self.0, err)))
+    }
+  }
+
+  macro_rules! parse_and_visit {
+    ($($method:ident => $visit:ident, $ty:ty, $kind:expr;)*) => {
+      $(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+          visitor.$visit(self.parse::<$ty>($kind)?)
+        }
+      )*
+    };
+  }
+
+  impl<'de> de::Deserializer<'de> for Value<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+      visitor.visit_str(self.0)
+    }
+
+    parse_and_visit! {
+      deserialize_bool => visit_bool, bool, "a boolean";
+      deserialize_u8 => visit_u8, u8, "an integer";
+      deserialize_u16 => visit_u16, u16, "an integer";
+      deserialize_u32 => visit_u32, u32, "an integer";
+      deserialize_u64 => visit_u64, u64, "an integer";
+      deserialize_u128 => visit_u128, u128, "an integer";
+      deserialize_i8 => visit_i8, i8, "an integer";
+      deserialize_i16 => visit_i16, i16, "an integer";
+      deserialize_i32 => visit_i32, i32, "an integer";
+      deserialize_i64 => visit_i64, i64, "an integer";
+      deserialize_i128 => visit_i128, i128, "an integer";
+      deserialize_f32 => visit_f32, f32, "a number";
+      deserialize_f64 => visit_f64, f64, "a number";
+      deserialize_char => visit_char, char, "a single character";
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+      visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+      visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+      self,
+      _name: &'static str,
+      _variants: &'static [&'static str],
+      visitor: V,
+    ) -> Result<V::Value, Error> {
+      visitor.visit_enum(self.0.into_deserializer())
+    }
+
+    serde::forward_to_deserialize_any! {
+      str string bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+  }
+
+  impl<'de, 'a> IntoDeserializer<'de, Error> for Value<'a> {
+    type Deserializer = Value<'a>;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+      self
+    }
+  }
+}
@@ -7,9 +7,13 @@
   feature = "async_smol"
 ))]
 
+use crate::core::extract::FromRequest;
+use crate::core::response::IntoResponse;
 use crate::{Request, Response};
 use async_trait::async_trait;
 use futures::future::BoxFuture;
+use std::future::Future;
+use std::marker::PhantomData;
 use std::sync::Arc;
 
 /// The core, unified `Handler` trait, powered by `async-trait`.
@@ -29,45 +33,159 @@ impl Handler for Arc<dyn Handler> {
 // --- Helper Functions and Structs (To be hidden by the macro) ---
 
 // A private struct to wrap a synchronous function.
-struct SyncFnHandler<F>(F);
+struct SyncFnHandler<F, R>(F, PhantomData<fn() -> R>);
 
 #[async_trait]
-impl<F> Handler for SyncFnHandler<F>
+impl<F, R> Handler for SyncFnHandler<F, R>
 where
-  F: for<'a> Fn(&'a Request) -> Response + Send + Sync,
+  F: for<'a> Fn(&'a Request) -> R + Send + Sync,
+  R: IntoResponse,
 {
   async fn handle(&self, request: &Request) -> Response {
-    (self.0)(request)
+    (self.0)(request).into_response()
   }
 }
 
 /// Wraps a synchronous function, turning it into a type that implements `Handler`.
-pub fn sync_h<F>(f: F) -> Arc<dyn Handler>
+/// The function can return anything implementing `IntoResponse`.
+pub fn sync_h<F, R>(f: F) -> Arc<dyn Handler>
 where
-  F: for<'a> Fn(&'a Request) -> Response + Send + Sync + 'static,
+  F: for<'a> Fn(&'a Request) -> R + Send + Sync + 'static,
+  R: IntoResponse + 'static,
 {
-  Arc::new(SyncFnHandler(f))
+  Arc::new(SyncFnHandler(f, PhantomData))
 }
 
 // A private struct to wrap an asynchronous function that returns a BoxFuture.
-struct AsyncFnHandler<F>(F);
+struct AsyncFnHandler<F, R>(F, PhantomData<fn() -> R>);
 
 #[async_trait]
-impl<F> Handler for AsyncFnHandler<F>
+impl<F, R> Handler for AsyncFnHandler<F, R>
 where
-  F: for<'a> Fn(&'a Request) -> BoxFuture<'a, Response> + Send + Sync,
+  F: for<'a> Fn(&'a Request) -> BoxFuture<'a, R> + Send + Sync,
+  R: IntoResponse,
 {
   async fn handle(&self, request: &Request) -> Response {
-    (self.0)(request).await
+    (self.0)(request).await.into_response()
   }
 }
 
 /// Wraps an asynchronous closure that returns a BoxFuture.
-pub fn async_h<F>(f: F) -> Arc<dyn Handler>
+/// The future can resolve to anything implementing `IntoResponse`.
+pub fn async_h<F, R>(f: F) -> Arc<dyn Handler>
 where
-  F: for<'a> Fn(&'a Request) -> BoxFuture<'a, Response> + Send + Sync + 'static,
+  F: for<'a> Fn(&'a Request) -> BoxFuture<'a, R> + Send + Sync + 'static,
+  R: IntoResponse + 'static,
 {
-  Arc::new(AsyncFnHandler(f))
+  Arc::new(AsyncFnHandler(f, PhantomData))
+}
+
+/// Synchronous functions whose arguments are all extractors, such as
+/// `fn show(Path(id): Path<u64>) -> Response`. `Args` is the tuple of argument types.
+pub trait SyncExtractFn<Args>: Send + Sync + 'static {
+  fn call(&self, request: &Request) -> Response;
+}
+
+/// Asynchronous functions whose arguments are all extractors, such as
+/// `async fn show(Path(id): Path<u64>) -> Response`. `Args` is the tuple of argument types.
+pub trait AsyncExtractFn<Args>: Send + Sync + 'static {
+  fn call<'a>(&'a self, request: &'a Request) -> BoxFuture<'a, Response>;
+}
+
+// Builds every argument in order and answers the first `Rejection` instead of calling `f`.
+macro_rules! impl_extract_fn {
+  ($($arg:ident),*) => {
+    impl<F, R, $($arg,)*> SyncExtractFn<($($arg,)*)> for F
+    where
+      F: Fn($($arg),*) -> R + Send + Sync + 'static,
+      R: IntoResponse,
+      $($arg: FromRequest,)*
+    {
+      #[allow(non_snake_case, unused_variables)]
+      fn call(&self, request: &Request) -> Response {
+        $(
+          let $arg = match $arg::from_request(request) {
+            Ok(value) => value,
+            Err(rejection) => return rejection.into_response(),
+          };
+        )*
+        (self)($($arg),*).into_response()
+      }
+    }
+
+    impl<F, Fut, R, $($arg,)*> AsyncExtractFn<($($arg,)*)> for F
+    where
+      F: Fn($($arg),*) -> Fut + Send + Sync + 'static,
+      Fut: Future<Output = R> + Send + 'static,
+      R: IntoResponse,
+      $($arg: FromRequest,)*
+    {
+      #[allow(non_snake_case, unused_variables)]
+      fn call<'a>(&'a self, request: &'a Request) -> BoxFuture<'a, Response> {
+        $(
+          let $arg = match $arg::from_request(request) {
+            Ok(value) => value,
+            Err(rejection) => return Box::pin(std::future::ready(rejection.into_response())),
+          };
+        )*
+        let future = (self)($($arg),*);
+        Box::pin(async move { future.await.into_response() })
+      }
+    }
+  };
+}
+
+impl_extract_fn!();
+impl_extract_fn!(A1);
+impl_extract_fn!(A1, A2);
+impl_extract_fn!(A1, A2, A3);
+impl_extract_fn!(A1, A2, A3, A4);
+impl_extract_fn!(A1, A2, A3, A4, A5);
+impl_extract_fn!(A1, A2, A3, A4, A5, A6);
+
+// A private struct to wrap a synchronous function taking extractors.
+struct SyncExtractHandler<F, Args>(F, PhantomData<fn() -> Args>);
+
+#[async_trait]
+impl<F, Args> Handler for SyncExtractHandler<F, Args>
+where
+  F: SyncExtractFn<Args>,
+{
+  async fn handle(&self, request: &Request) -> Response {
+    self.0.call(request)
+  }
+}
+
+/// Wraps a synchronous function taking extractors, turning it into a type that implements
+/// `Handler`.
+pub fn sync_extract_h<F, Args>(f: F) -> Arc<dyn Handler>
+where
+  F: SyncExtractFn<Args>,
+  Args: 'static,
+{
+  Arc::new(SyncExtractHandler(f, PhantomData))
+}
+
+// A private struct to wrap an asynchronous function taking extractors.
+struct AsyncExtractHandler<F, Args>(F, PhantomData<fn() -> Args>);
+
+#[async_trait]
+impl<F, Args> Handler for AsyncExtractHandler<F, Args>
+where
+  F: AsyncExtractFn<Args>,
+{
+  async fn handle(&self, request: &Request) -> Response {
+    self.0.call(request).await
+  }
+}
+
+/// Wraps an `async fn` taking extractors, turning it into a type that implements `Handler`.
+pub fn async_extract_h<F, Args>(f: F) -> Arc<dyn Handler>
+where
+  F: AsyncExtractFn<Args>,
+  Args: 'static,
+{
+  Arc::new(AsyncExtractHandler(f, PhantomData))
 }
 
 /// Simplifies handler creation for synchronous builds.
@@ -99,3 +217,32 @@ macro_rules! handler {
     $crate::core::handler::async_h(move |req| Box::pin($handler_fn(req)))
   };
 }
+
+/// Builds a handler from a synchronous function whose arguments are extractors.
+///
+/// This macro expands to a call to the `sync_extract_h` helper function.
+/// Each argument is built with `FromRequest` before the function runs, and
+/// the first one that fails answers the request with its `Rejection`.
+#[macro_export]
+#[cfg(feature = "sync")]
+macro_rules! extract_handler {
+  ($handler_fn:expr) => {
+    $crate::core::handler::sync_extract_h($handler_fn)
+  };
+}
+
+/// Builds a handler from an `async fn` whose arguments are extractors.
+///
+/// This macro expands to a call to the `async_extract_h` helper function.
+/// Each argument is built with `FromRequest` before the function runs, and
+/// the first one that fails answers the request with its `Rejection`.
+#[macro_export]
+#[cfg(all(
+  any(feature = "async_tokio", feature = "async_std", feature = "async_smol"),
+  not(feature = "sync")
+))]
+macro_rules! extract_handler {
+  ($handler_fn:expr) => {
+    $crate::core::handler::async_extract_h($handler_fn)
+  };
+}
@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+/// Bounds applied while a request is read off the wire.
+///
+/// Every server starts with `ServerLimits::default()`; replace it with `set_limits`,
+/// e.g. `ServerLimits { max_body_size: 1 << 20, ..Default::default() }`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ServerLimits {
+  /// Longest wait for each header line once a request has started (408 when exceeded).
+  pub header_read_timeout: Duration,
+  /// Longest wait for the next part of a body. Framed bodies answer 408; an unframed body simply ends.
+  pub body_read_timeout: Duration,
+  /// Size cap for the request line plus header section (414 while still in the request line, 431 after).
+  pub max_header_bytes: usize,
+  /// Maximum number of header fields (431).
+  pub max_header_count: usize,
+  /// Maximum body size in bytes, after chunked decoding (413).
+  pub max_body_size: usize,
+  /// Maximum length of the request target (414).
+  pub max_uri_length: usize,
+}
+
+impl Default for ServerLimits {
+  fn default() -> Self {
+    ServerLimits {
+      header_read_timeout: Duration::from_millis(50),
+      body_read_timeout: Duration::from_millis(50),
+      max_header_bytes: 8 * 1024,
+      max_header_count: 100,
+      max_body_size: 10 * 1024 * 1024,
+      max_uri_length: 2000,
+    }
+  }
+}
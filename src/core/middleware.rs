@@ -0,0 +1,135 @@
+#![cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+
+use crate::core::cors::CorsPolicy;
+use crate::core::error_pages::ErrorPages;
+use crate::core::handler::Handler;
+use crate::core::router::RouteTree;
+use crate::core::state::SharedState;
+use crate::{Request, Response};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use std::sync::Arc;
+
+/// Code that runs around a route's handler.
+///
+/// A middleware sees the request before the handler does and may change it, answer on its
+/// own by not calling `next`, or pass it on with `next.run(request).await` and adjust the
+/// response that comes back.
+///
+/// Middleware goes on a single route (`add_route_with`), on a `Router` group, or on the whole
+/// server (`add_middleware`), where it also sees 404s, 405s, static files and preflight answers.
+#[async_trait]
+pub trait Middleware: Send + Sync {
+  async fn call(&self, request: &mut Request, next: Next<'_>) -> Response;
+}
+
+/// The rest of the chain after the current middleware: the remaining middleware, then the
+/// route's handler.
+pub struct Next<'a> {
+  middleware: &'a [Arc<dyn Middleware>],
+  endpoint: Endpoint<'a>,
+}
+
+enum Endpoint<'a> {
+  Handler(&'a dyn Handler),
+  Server(&'a Dispatcher),
+}
+
+impl<'a> Next<'a> {
+  pub fn new(middleware: &'a [Arc<dyn Middleware>], handler: &'a dyn Handler) -> Self {
+    Next {
+      middleware,
+      endpoint: Endpoint::Handler(handler),
+    }
+  }
+
+  /// Runs the rest of the chain for `request`.
+  pub async fn run(self, request: &mut Request) -> Response {
+    match self.middleware.split_first() {
+      Some((first, rest)) => {
+        let next = Next {
+          middleware: rest,
+          endpoint: self.endpoint,
+        };
+        first.call(request, next).await
+      }
+      None => match self.endpoint {
+        Endpoint::Handler(handler) => handler.handle(request).await,
+        Endpoint::Server(dispatcher) => dispatcher.route(request).await,
+      },
+    }
+  }
+}
+
+/// What a server does with a parsed request: its global middleware around routing, 405
+/// answers, static files and the fallback.
+pub(crate) struct Dispatcher {
+  routes: Arc<RouteTree>,
+  file_bases: Vec<String>,
+  error_pages: Arc<ErrorPages>,
+  cors: Option<Arc<CorsPolicy>>,
+  middleware: Vec<Arc<dyn Middleware>>,
+  state: Arc<SharedState>,
+}
+
+impl Dispatcher {
+  /// Bundles a server's setup for its connection tasks, with the CORS policy as the
+  /// outermost global middleware.
+  pub(crate) fn new(
+    routes: &Arc<RouteTree>,
+    file_bases: &[String],
+    error_pages: &Arc<ErrorPages>,
+    cors: Option<&Arc<CorsPolicy>>,
+    middleware: &[Arc<dyn Middleware>],
+    state: &Arc<SharedState>,
+  ) -> Self {
+    Dispatcher {
+      routes: Arc::clone(routes),
+      file_bases: file_bases.to_vec(),
+      error_pages: Arc::clone(error_pages),
+      cors: cors.cloned(),
+      middleware: cors
+        .map(|policy| Arc::clone(policy) as Arc<dyn Middleware>)
+        .into_iter()
+        .chain(middleware.iter().cloned())
+        .collect(),
+      state: Arc::clone(state),
+    }
+  }
+
+  /// Answers a request the parser refused, or an unreadable first request (`request` is then
+  /// blank): the rendered error page with the server's CORS headers, as the request never
+  /// reaches the middleware.
+  pub(crate) fn refuse(&self, request: &Request, response: Response) -> Response {
+    let mut response = self.error_pages.render(request, response);
+    if let Some(policy) = &self.cors {
+      response.headers.extend(policy.header_lines(request.origin()));
+    }
+    response
+  }
+
+  pub(crate) async fn handle(&self, request: &mut Request) -> Response {
+    request.set_routes(&self.routes);
+    request.set_state(&self.state);
+    let next = Next {
+      middleware: &self.middleware,
+      endpoint: Endpoint::Server(self),
+    };
+    next.run(request).await
+  }
+
+  // Boxed because the matched route's own middleware runs through `Next::run` again
+  fn route<'a>(&'a self, request: &'a mut Request) -> BoxFuture<'a, Response> {
+    Box::pin(async move {
+      match request.route(&self.routes, &self.file_bases, &self.error_pages).await {
+        Some(response) => response,
+        None => self.error_pages.not_found(request).await,
+      }
+    })
+  }
+}
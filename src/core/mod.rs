@@ -1,9 +1,18 @@
+pub mod chunked;
 pub mod handler;
+pub mod limits;
+pub mod middleware;
 pub mod request;
 pub mod request_handler;
 pub mod request_type;
 pub mod response;
+pub mod route_constraint;
+pub mod router;
+pub mod state;
 pub mod status_code;
 pub mod test_utils;
 pub mod utils;
 pub mod cors;
+pub mod error_pages;
+pub mod extensions;
+pub mod extract;
@@ -3,224 +3,121 @@
 /// This macro abstracts the common logic of reading and parsing an HTTP request
 /// from a TCP stream, while allowing the caller to specify the runtime-specific
 /// types and traits (stream type, BufReader, and I/O extension traits).
-pub const READ_TIMEOUT_MS: u64 = 50;
-pub const BODY_READ_LIMIT_BYTES: u64 = 512;
-
 macro_rules! create_async_parse_stream {
     (
         $(#[$outer:meta])*
         $func_name:ident,
-        $stream_ty:ty,
-        $buf_reader:ty,
+        $reader_ty:ty,
         $async_read_ext:path,
         $async_buf_read_ext:path
     ) => {
         $(#[$outer])*
+        /// Returns `None` when the peer sends nothing before `first_line_timeout` or closes the connection.
+        /// Bytes past the end of this request stay buffered in `reader` for the next call.
         pub async fn $func_name(
-            stream: &mut $stream_ty,
-            routes: &std::collections::HashMap<(crate::core::request_type::Rt, String), crate::core::request_handler::Rh>,
-            file_bases: &[String],
-        ) -> (crate::core::request::Request, Option<crate::core::response::Response>) {
+            reader: &mut $reader_ty,
+            limits: &crate::core::limits::ServerLimits,
+            first_line_timeout: std::time::Duration,
+            read_unframed_body: bool,
+        ) -> Option<(crate::core::request::Request, Option<crate::core::response::Response>)> {
             use $async_read_ext;
             use $async_buf_read_ext;
+            use crate::core::status_code::StatusCode;
+            use crate::runtime::r#async::shared::timeout;
 
-            let mut reader = <$buf_reader>::new(stream);
-            let mut raw = String::new();
-            let header_timeout = std::time::Duration::from_millis(crate::core::request::READ_TIMEOUT_MS);
+            let mut raw: Vec<u8> = Vec::new();
+            let mut wait = first_line_timeout;
 
-            // Read headers only
+            // Read headers only, never buffering more than the header budget
             loop {
-                let mut line = String::new();
-                #[cfg(feature = "async_tokio")]
-                {
-                    let read_fut = reader.read_line(&mut line);
-                    let sleep = tokio::time::sleep(header_timeout);
-                    futures::pin_mut!(read_fut, sleep);
-                    match futures::future::select(read_fut, sleep).await {
-                        futures::future::Either::Left((Ok(n), _)) if n > 0 => {
-                            raw.push_str(&line);
-                            if raw.contains("\r\n\r\n") {
-                                break;
-                            }
-                        }
-                        _ => break,
-                    }
+                let budget = (limits.max_header_bytes + 1).saturating_sub(raw.len());
+                let mut line = Vec::new();
+                let result = timeout(wait, (&mut *reader).take(budget as u64).read_until(b'\n', &mut line)).await;
+                raw.extend_from_slice(&line);
+                match result {
+                    Some(Ok(n)) if n > 0 => {}
+                    None if !raw.is_empty() => return crate::core::request::refused(&raw, StatusCode::RequestTimeout),
+                    _ => break,
                 }
-
-                #[cfg(all(feature = "async_std", not(feature = "async_tokio")))]
-                {
-                    let read_fut = reader.read_line(&mut line);
-                    let sleep = async_std::task::sleep(header_timeout);
-                    futures::pin_mut!(read_fut, sleep);
-                    match futures::future::select(read_fut, sleep).await {
-                        futures::future::Either::Left((Ok(n), _)) if n > 0 => {
-                            raw.push_str(&line);
-                            if raw.contains("\r\n\r\n") {
-                                break;
-                            }
-                        }
-                        _ => break,
-                    }
+                if raw.ends_with(b"\r\n\r\n") {
+                    break;
                 }
-
-                #[cfg(all(feature = "async_smol", not(any(feature = "async_tokio", feature = "async_std"))))]
-                {
-                    let read_fut = reader.read_line(&mut line);
-                    let sleep = smol::Timer::after(header_timeout);
-                    futures::pin_mut!(read_fut, sleep);
-                    match futures::future::select(read_fut, sleep).await {
-                        futures::future::Either::Left((Ok(n), _)) if n > 0 => {
-                            raw.push_str(&line);
-                            if raw.contains("\r\n\r\n") {
-                                break;
-                            }
-                        }
-                        _ => break,
-                    }
+                if let Some(status) = crate::core::request::head_limit_error(&raw, limits) {
+                    return crate::core::request::refused(&raw, status);
                 }
+                wait = limits.header_read_timeout;
+            }
+            if raw.is_empty() {
+                return None;
             }
 
-            // Extract method from the first line
-            let method = raw
-                .lines()
-                .next()
-                .and_then(|l| l.split_whitespace().next())
-                .unwrap_or("");
-
-            let (content_length, has_transfer_encoding) = crate::core::request::extract_body_headers(&raw);
-
-            // Read declared body size. For POST/PUT/DELETE/PATCH without Content-Length or Transfer-Encoding, fall back to a timed read.
-            if content_length > 0 {
-                // Read exactly content_length bytes
-                let mut buf = vec![0; content_length];
-                let _ = reader.read_exact(&mut buf).await;
-                raw.push_str(&String::from_utf8_lossy(&buf));
-            } else if method == "POST" || method == "PUT" || method == "DELETE" || method == "PATCH" {
-                if has_transfer_encoding {
-                    // Read all until EOF for POST/PUT/DELETE/PATCH with Transfer-Encoding and without Content-Length
-                    let mut rest = String::new();
-
-                    #[cfg(feature = "async_tokio")]
-                    {
-                        use std::time::Duration;
-                        let mut limited = reader.take(crate::core::request::BODY_READ_LIMIT_BYTES);
-                        let read_fut = limited.read_to_string(&mut rest);
-                        let sleep = tokio::time::sleep(Duration::from_millis(crate::core::request::READ_TIMEOUT_MS));
-                        futures::pin_mut!(read_fut, sleep);
-                        if matches!(
-                            futures::future::select(read_fut, sleep).await,
-                            futures::future::Either::Left((Ok(_), _))
-                        ) {
-                            raw.push_str(&rest);
-                        }
-                    }
-
-                    #[cfg(all(feature = "async_std", not(feature = "async_tokio")))]
-                    {
-                        use std::time::Duration;
-                        let mut limited = reader.take(crate::core::request::BODY_READ_LIMIT_BYTES);
-                        let read_fut = limited.read_to_string(&mut rest);
-                        let sleep = async_std::task::sleep(Duration::from_millis(crate::core::request::READ_TIMEOUT_MS));
-                        futures::pin_mut!(read_fut, sleep);
-                        if matches!(
-                            futures::future::select(read_fut, sleep).await,
-                            futures::future::Either::Left((Ok(_), _))
-                        ) {
-                            raw.push_str(&rest);
-                        }
-                    }
-
-                    #[cfg(all(feature = "async_smol", not(any(feature = "async_tokio", feature = "async_std"))))]
-                    {
-                        use std::time::Duration;
-                        let mut limited = reader.take(crate::core::request::BODY_READ_LIMIT_BYTES);
-                        let read_fut = limited.read_to_string(&mut rest);
-                        let sleep = smol::Timer::after(Duration::from_millis(crate::core::request::READ_TIMEOUT_MS));
-                        futures::pin_mut!(read_fut, sleep);
-                        if matches!(
-                            futures::future::select(read_fut, sleep).await,
-                            futures::future::Either::Left((Ok(_), _))
-                        ) {
-                            raw.push_str(&rest);
-                        }
-                    }
-                } else {
-                    // No length hints; read opportunistically with a short timeout to avoid hanging.
-                    let mut buf: Vec<u8> = Vec::new();
-
-                    #[cfg(feature = "async_tokio")]
-                    {
-                        use std::time::Duration;
-                        let mut chunk = [0u8; 128];
-                        loop {
-                            let read_fut = reader.read(&mut chunk);
-                            let sleep = tokio::time::sleep(Duration::from_millis(crate::core::request::READ_TIMEOUT_MS));
-                            futures::pin_mut!(read_fut, sleep);
-                            match futures::future::select(read_fut, sleep).await {
-                                futures::future::Either::Left((Ok(0), _)) => break,
-                                futures::future::Either::Left((Ok(n), _)) => {
-                                    buf.extend_from_slice(&chunk[..n]);
-                                    if buf.len() as u64 >= crate::core::request::BODY_READ_LIMIT_BYTES {
-                                        break;
-                                    }
-                                }
-                                futures::future::Either::Left((Err(_), _)) => break,
-                                futures::future::Either::Right(_) => break,
-                            }
-                        }
-                    }
-
-                    #[cfg(all(feature = "async_std", not(feature = "async_tokio")))]
-                    {
-                        use std::time::Duration;
-                        let mut chunk = [0u8; 128];
-                        loop {
-                            let read_fut = reader.read(&mut chunk);
-                            let sleep = async_std::task::sleep(Duration::from_millis(crate::core::request::READ_TIMEOUT_MS));
-                            futures::pin_mut!(read_fut, sleep);
-                            match futures::future::select(read_fut, sleep).await {
-                                futures::future::Either::Left((Ok(0), _)) => break,
-                                futures::future::Either::Left((Ok(n), _)) => {
-                                    buf.extend_from_slice(&chunk[..n]);
-                                    if buf.len() as u64 >= crate::core::request::BODY_READ_LIMIT_BYTES {
-                                        break;
-                                    }
-                                }
-                                futures::future::Either::Left((Err(_), _)) => break,
-                                futures::future::Either::Right(_) => break,
-                            }
+            let (method, content_length, chunked) = match crate::core::request::extract_body_headers(&raw) {
+                Ok(framing) => framing,
+                Err(status) => return crate::core::request::refused(&raw, status),
+            };
+            let head_len = raw.len();
+            let mut trailers = Vec::new();
+
+            // Decode chunked bodies, else read the declared size. For POST/PUT/DELETE/PATCH without either, fall back to a timed read.
+            if chunked {
+                let mut decoder =
+                    crate::core::chunked::ChunkedDecoder::with_trailer_limits(limits.max_header_count, limits.max_header_bytes);
+                while !decoder.is_done() {
+                    let available = match timeout(limits.body_read_timeout, reader.fill_buf()).await {
+                        None => return crate::core::request::refused(&raw, StatusCode::RequestTimeout),
+                        Some(Ok([])) | Some(Err(_)) => break,
+                        Some(Ok(available)) => available,
+                    };
+                    // Only consume what the decoder used; the rest may be a pipelined request
+                    let used = match decoder.feed(available) {
+                        Ok(used) => used,
+                        Err(crate::core::chunked::ChunkedError::TrailersTooLarge) => {
+                            return crate::core::request::refused(&raw, StatusCode::RequestHeaderFieldsTooLarge)
                         }
+                        Err(_) => break,
+                    };
+                    reader.consume(used);
+                    if decoder.body().len() > limits.max_body_size {
+                        return crate::core::request::refused(&raw, StatusCode::PayloadTooLarge);
                     }
-
-                    #[cfg(all(feature = "async_smol", not(any(feature = "async_tokio", feature = "async_std"))))]
-                    {
-                        use std::time::Duration;
-                        let mut chunk = [0u8; 128];
-                        loop {
-                            let read_fut = reader.read(&mut chunk);
-                            let sleep = smol::Timer::after(Duration::from_millis(crate::core::request::READ_TIMEOUT_MS));
-                            futures::pin_mut!(read_fut, sleep);
-                            match futures::future::select(read_fut, sleep).await {
-                                futures::future::Either::Left((Ok(0), _)) => break,
-                                futures::future::Either::Left((Ok(n), _)) => {
-                                    buf.extend_from_slice(&chunk[..n]);
-                                    if buf.len() as u64 >= crate::core::request::BODY_READ_LIMIT_BYTES {
-                                        break;
-                                    }
-                                }
-                                futures::future::Either::Left((Err(_), _)) => break,
-                                futures::future::Either::Right(_) => break,
-                            }
-                        }
+                }
+                if !decoder.is_done() {
+                    return crate::core::request::refused(&raw, StatusCode::BadRequest);
+                }
+                trailers = crate::core::request::append_chunked_body(&mut raw, decoder);
+            } else if content_length > limits.max_body_size {
+                return crate::core::request::refused(&raw, StatusCode::PayloadTooLarge);
+            } else if content_length > 0 {
+                // Read up to content_length bytes; a body cut short by EOF is kept as-is instead of being zero-padded
+                while raw.len() - head_len < content_length {
+                    let available = match timeout(limits.body_read_timeout, reader.fill_buf()).await {
+                        None => return crate::core::request::refused(&raw, StatusCode::RequestTimeout),
+                        Some(Ok([])) | Some(Err(_)) => break,
+                        Some(Ok(available)) => available,
+                    };
+                    let n = available.len().min(content_length - (raw.len() - head_len));
+                    raw.extend_from_slice(&available[..n]);
+                    reader.consume(n);
+                }
+            } else if read_unframed_body && (method == "POST" || method == "PUT" || method == "DELETE" || method == "PATCH") {
+                // No length hints; whatever arrives before the body timeout is the body.
+                // Skipped on persistent connections, where those bytes belong to the next request.
+                while let Some(Ok(available)) = timeout(limits.body_read_timeout, reader.fill_buf()).await {
+                    if available.is_empty() {
+                        break;
                     }
-
-                    if !buf.is_empty() {
-                        raw.push_str(&String::from_utf8_lossy(&buf));
+                    let n = available.len();
+                    raw.extend_from_slice(available);
+                    reader.consume(n);
+                    if raw.len() - head_len > limits.max_body_size {
+                        return crate::core::request::refused(&raw, StatusCode::PayloadTooLarge);
                     }
                 }
             }
 
-            crate::core::request::Request::parse_raw_async(raw, routes, file_bases).await
+            let (mut request, error) = crate::core::request::Request::parse_raw(raw, limits);
+            request.trailers = trailers;
+            Some((request, error))
         }
     };
 }
@@ -231,20 +128,141 @@ macro_rules! create_async_parse_stream {
   feature = "async_std",
   feature = "async_smol"
 ))]
-fn extract_body_headers(raw: &str) -> (usize, bool) {
-  let mut content_length = 0usize;
-  let mut has_transfer_encoding = false;
-  for line in raw.lines() {
-    let lower = line.to_ascii_lowercase();
-    if lower.starts_with("content-length:") {
-      if let Some(len) = line.split(':').nth(1).and_then(|v| v.trim().parse::<usize>().ok()) {
-        content_length = len;
+fn header_end(raw: &[u8]) -> Option<usize> {
+  raw.windows(4).position(|w| w == b"\r\n\r\n")
+}
+
+/// Splits a raw request into its head (request line and headers) and its body bytes.
+#[cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+fn split_head(raw: &[u8]) -> (&[u8], &[u8]) {
+  match header_end(raw) {
+    Some(pos) => (&raw[..pos], &raw[pos + 4..]),
+    None => (raw, &[]),
+  }
+}
+
+#[cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+/// Reads the method and body framing (Content-Length, chunked) from a request head.
+/// Ambiguous framing is refused with 400 so a proxy in front can never disagree about where the body ends.
+fn extract_body_headers(raw: &[u8]) -> Result<(String, usize, bool), StatusCode> {
+  let head = String::from_utf8_lossy(split_head(raw).0);
+  let method = head
+    .lines()
+    .next()
+    .and_then(|l| l.split_whitespace().next())
+    .unwrap_or("")
+    .to_string();
+  let mut content_length: Option<usize> = None;
+  let mut transfer_encoding: Option<String> = None;
+  for line in head.lines().skip(1) {
+    let Some((name, value)) = line.split_once(':') else {
+      continue;
+    };
+    let value = value.trim();
+    if name.eq_ignore_ascii_case("content-length") {
+      // Digits only, so "+5", "-1" and "5, 5" are refused along with any repeated field
+      if content_length.is_some() || value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(StatusCode::BadRequest);
+      }
+      // A value too big for usize is over any body limit, so saturate and let the size check answer 413
+      content_length = Some(value.parse::<usize>().unwrap_or(usize::MAX));
+    } else if name.eq_ignore_ascii_case("transfer-encoding") {
+      // Repeated fields form one comma-separated list of codings
+      let codings = transfer_encoding.get_or_insert_with(String::new);
+      if !codings.is_empty() {
+        codings.push(',');
       }
-    } else if lower.starts_with("transfer-encoding:") {
-      has_transfer_encoding = true;
+      codings.push_str(value);
     }
   }
-  (content_length, has_transfer_encoding)
+  let chunked = match transfer_encoding {
+    None => false,
+    // Both framings at once is the classic request smuggling vector
+    Some(_) if content_length.is_some() => return Err(StatusCode::BadRequest),
+    // Only a final "chunked" coding tells where the body ends
+    Some(codings) => match codings.rsplit(',').next().map(str::trim) {
+      Some(last) if last.eq_ignore_ascii_case("chunked") => true,
+      _ => return Err(StatusCode::BadRequest),
+    },
+  };
+  Ok((method, content_length.unwrap_or(0), chunked))
+}
+
+#[cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+pub(crate) fn error_response(status: StatusCode) -> Response {
+  Response {
+    status,
+    content_type: String::new(),
+    content: Vec::new(),
+    headers: Vec::new(),
+    stream: None,
+    extensions: Extensions::new(),
+  }
+}
+
+/// A refused request, answered with `status`. The request keeps the header fields read so
+/// far, so the answer can still carry CORS headers for its `Origin`.
+#[cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+pub(crate) fn refused(raw: &[u8], status: StatusCode) -> Option<(Request, Option<Response>)> {
+  Some((Request::with_head_fields(raw), Some(error_response(status))))
+}
+
+/// Checks a partially read request head against the header limits.
+#[cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+pub(crate) fn head_limit_error(raw: &[u8], limits: &ServerLimits) -> Option<StatusCode> {
+  if raw.len() > limits.max_header_bytes {
+    // Running out of room before the request line ends means the target itself is too long
+    return Some(if raw.contains(&b'\n') {
+      StatusCode::RequestHeaderFieldsTooLarge
+    } else {
+      StatusCode::UriTooLong
+    });
+  }
+  let fields = raw
+    .split(|&b| b == b'\n')
+    .skip(1)
+    .filter(|line| !line.is_empty() && *line != b"\r")
+    .count();
+  (fields > limits.max_header_count).then_some(StatusCode::RequestHeaderFieldsTooLarge)
+}
+
+/// Appends a decoded chunked body to a raw request head and returns its trailer fields, which
+/// are kept apart from the header section (RFC 9110, section 6.5.1).
+#[cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+pub(crate) fn append_chunked_body(raw: &mut Vec<u8>, decoder: ChunkedDecoder) -> Vec<(String, String)> {
+  let (body, trailers) = decoder.into_parts();
+  raw.extend_from_slice(&body);
+  trailers
 }
 
 #[cfg(any(
@@ -253,7 +271,58 @@ fn extract_body_headers(raw: &str) -> (usize, bool) {
   feature = "async_std",
   feature = "async_smol"
 ))]
-use crate::core::request_handler::Rh;
+use crate::core::chunked::ChunkedDecoder;
+#[cfg(feature = "sync")]
+use crate::core::chunked::ChunkedError;
+#[cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+use crate::core::limits::ServerLimits;
+#[cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+use crate::core::error_pages::ErrorPages;
+#[cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+use crate::core::extensions::Extensions;
+#[cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+use crate::core::middleware::Next;
+#[cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+use crate::core::router::{RouteMatch, RouteTree, UrlError};
+#[cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+use crate::core::state::SharedState;
+#[cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+use std::sync::Arc;
 #[cfg(any(
   feature = "sync",
   feature = "async_tokio",
@@ -267,7 +336,7 @@ use crate::core::request_type::{RequestType, Rt};
   feature = "async_std",
   feature = "async_smol"
 ))]
-use crate::core::response::Response;
+use crate::core::response::{IntoResponse, Response};
 #[cfg(any(
   feature = "sync",
   feature = "async_tokio",
@@ -281,9 +350,27 @@ use crate::core::status_code::StatusCode;
   feature = "async_std",
   feature = "async_smol"
 ))]
-use std::collections::{BTreeMap, HashMap};
+use crate::core::utils::form_decode;
+#[cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+use std::borrow::Cow;
+#[cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+use std::collections::HashMap;
+#[cfg(feature = "sync")]
+use std::io::BufReader;
 #[cfg(feature = "sync")]
 use std::net::TcpStream;
+#[cfg(feature = "sync")]
+use std::time::Duration;
 #[cfg(any(
   feature = "sync",
   feature = "async_tokio",
@@ -304,8 +391,7 @@ use tokio;
 create_async_parse_stream!(
   #[cfg(feature = "async_tokio")]
   parse_stream_tokio,
-  tokio::net::TcpStream,
-  tokio::io::BufReader<_>,
+  tokio::io::BufReader<tokio::net::TcpStream>,
   tokio::io::AsyncReadExt,
   tokio::io::AsyncBufReadExt
 );
@@ -313,17 +399,15 @@ create_async_parse_stream!(
 create_async_parse_stream!(
   #[cfg(feature = "async_std")]
   parse_stream_async_std,
-  async_std::net::TcpStream,
-  async_std::io::BufReader<_>,
+  async_std::io::BufReader<async_std::net::TcpStream>,
   async_std::io::ReadExt,
-  async_std::io::BufReadExt
+  futures_lite::io::AsyncBufReadExt
 );
 
 create_async_parse_stream!(
   #[cfg(feature = "async_smol")]
   parse_stream_smol,
-  smol::net::TcpStream,
-  futures_lite::io::BufReader<_>,
+  futures_lite::io::BufReader<smol::net::TcpStream>,
   futures_lite::io::AsyncReadExt,
   futures_lite::io::AsyncBufReadExt
 );
@@ -339,8 +423,18 @@ pub struct Request {
   pub path: String,
   pub version: String,
   pub headers: Vec<(String, String)>,
-  pub body: String,
+  pub body: Vec<u8>,
+  /// Trailer fields sent after a chunked body. They are never merged into `headers`.
+  pub trailers: Vec<(String, String)>,
+  /// Query string parameters, decoded as `application/x-www-form-urlencoded`.
   pub params: HashMap<String, String>,
+  /// Values captured by the matched route's `{name}` segments; empty until the request is routed.
+  pub path_params: HashMap<String, String>,
+  /// Typed values attached by middleware for the handler, e.g. the authenticated user.
+  pub extensions: Extensions,
+  path_names: Vec<String>,
+  routes: Option<Arc<RouteTree>>,
+  state: Option<Arc<SharedState>>,
 }
 
 #[cfg(any(
@@ -350,24 +444,6 @@ pub struct Request {
   feature = "async_smol"
 ))]
 impl Request {
-  fn extract_params(route: &str, path: &str) -> HashMap<String, String> {
-    let mut sorted: BTreeMap<String, String> = BTreeMap::new();
-    let route_parts = route.split('/').collect::<Vec<_>>();
-    let path_parts = path.split('/').collect::<Vec<_>>();
-    if route_parts.len() != path_parts.len() {
-      return HashMap::new();
-    }
-    for (i, part) in route_parts.iter().enumerate() {
-      if part.starts_with('{') && part.ends_with('}') {
-        let key = part.trim_matches(&['{', '}'][..]).to_string();
-        sorted.insert(key, path_parts[i].to_string());
-      } else if *part != path_parts[i] {
-        return HashMap::new();
-      }
-    }
-    sorted.into_iter().collect()
-  }
-
   pub fn origin(&self) -> Option<&str> {
     self
       .headers
@@ -376,239 +452,250 @@ impl Request {
       .map(|(_, v)| v.as_str())
   }
 
-  #[cfg(feature = "sync")]
-  pub fn parse_stream_sync(
-    stream: &TcpStream,
-    routes: &HashMap<(Rt, String), Rh>,
-    file_bases: &[String],
-  ) -> (Self, Option<Response>) {
-    use std::io::{BufRead, BufReader, Read};
-    use std::time::Duration;
+  /// Whether the client allows the connection to stay open after this request.
+  /// Only HTTP/1.1 requests without a `Connection: close` token qualify.
+  pub fn keep_alive(&self) -> bool {
+    let close_requested = self
+      .headers
+      .iter()
+      .filter(|(k, _)| k.eq_ignore_ascii_case("connection"))
+      .flat_map(|(_, v)| v.split(','))
+      .any(|token| token.trim().eq_ignore_ascii_case("close"));
+    self.version == "HTTP/1.1" && !close_requested
+  }
 
-    let mut reader = BufReader::new(stream);
-    let mut raw = String::new();
-    let header_timeout = Duration::from_millis(READ_TIMEOUT_MS);
-    let _ = stream.set_read_timeout(Some(header_timeout));
+  /// Returns the body as UTF-8 text, failing if it holds invalid UTF-8.
+  pub fn text(&self) -> Result<&str, std::str::Utf8Error> {
+    std::str::from_utf8(&self.body)
+  }
+
+  /// Returns the body as text, replacing invalid UTF-8 sequences with `U+FFFD`.
+  pub fn text_lossy(&self) -> Cow<'_, str> {
+    String::from_utf8_lossy(&self.body)
+  }
+
+  /// Parses the path parameter `name` captured by the matched route, e.g.
+  /// `request.param::<u64>("id")`. A `ParamError` converts into a 400 response.
+  pub fn param<T>(&self, name: &str) -> Result<T, ParamError>
+  where
+    T: FromStr,
+    T::Err: Display,
+  {
+    let value = self
+      .path_params
+      .get(name)
+      .ok_or_else(|| ParamError::Missing(name.to_string()))?;
+    value.parse().map_err(|err: T::Err| ParamError::Invalid {
+      name: name.to_string(),
+      value: value.clone(),
+      reason: err.to_string(),
+    })
+  }
+
+  /// The path parameters as `(name, value)` pairs, in the order the matched route's pattern
+  /// captures them. Parameters set by hand on an unrouted request come sorted by name.
+  pub(crate) fn path_values(&self) -> Vec<(&str, &str)> {
+    if self.path_names.is_empty() {
+      let mut values: Vec<(&str, &str)> = self.path_params.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+      values.sort();
+      return values;
+    }
+    self
+      .path_names
+      .iter()
+      .filter_map(|name| self.path_params.get(name).map(|value| (name.as_str(), value.as_str())))
+      .collect()
+  }
 
-    // Read only headers
+  /// Builds the path of the route registered as `name` on the server that routed this
+  /// request, e.g. for a `Location` header. See `RouteTree::url_for`.
+  pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Result<String, UrlError> {
+    match &self.routes {
+      Some(routes) => routes.url_for(name, params),
+      None => Err(UrlError::UnknownRoute(name.to_string())),
+    }
+  }
+
+  /// Deserializes the body as JSON.
+  #[cfg(feature = "json")]
+  pub fn json<T: serde::de::DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+    serde_json::from_slice(&self.body)
+  }
+
+  /// Reads the next request on a connection. Routing is left to the caller.
+  /// Returns `None` when the peer sends nothing before `first_line_timeout` or closes the connection.
+  /// Bytes past the end of this request stay buffered in `reader` for the next call.
+  #[cfg(feature = "sync")]
+  pub fn parse_stream_sync(
+    reader: &mut BufReader<&TcpStream>,
+    limits: &ServerLimits,
+    first_line_timeout: Duration,
+    read_unframed_body: bool,
+  ) -> Option<(Self, Option<Response>)> {
+    use std::io::{BufRead, ErrorKind, Read};
+
+    let is_timeout = |e: &std::io::Error| matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut);
+    let stream = *reader.get_ref();
+    let mut raw: Vec<u8> = Vec::new();
+    let _ = stream.set_read_timeout(Some(first_line_timeout));
+
+    // Read only headers, never buffering more than the header budget
     loop {
-      let mut line = String::new();
-      if reader.read_line(&mut line).ok().filter(|&n| n > 0).is_none() {
-        break;
+      let budget = (limits.max_header_bytes + 1).saturating_sub(raw.len());
+      let mut line = Vec::new();
+      let result = reader.by_ref().take(budget as u64).read_until(b'\n', &mut line);
+      raw.extend_from_slice(&line);
+      match result {
+        Ok(n) if n > 0 => {}
+        Err(e) if is_timeout(&e) && !raw.is_empty() => return refused(&raw, StatusCode::RequestTimeout),
+        _ => break,
       }
-      raw.push_str(&line);
-      if raw.contains("\r\n\r\n") {
+      if raw.ends_with(b"\r\n\r\n") {
         break;
       }
+      if let Some(status) = head_limit_error(&raw, limits) {
+        return refused(&raw, status);
+      }
+      let _ = stream.set_read_timeout(Some(limits.header_read_timeout));
+    }
+    if raw.is_empty() {
+      return None;
     }
 
-    // Extract method from the first line
-    let method = raw
-      .lines()
-      .next()
-      .and_then(|l| l.split_whitespace().next())
-      .unwrap_or("");
-
-    let (content_length, has_transfer_encoding) = extract_body_headers(&raw);
-    let _ = stream.set_read_timeout(None);
-
-    // Require Content-Length when provided; otherwise read with a short timeout to avoid blocking on keep-alive.
-    if content_length > 0 {
-      // Read exactly content_length
-      let mut buf = vec![0; content_length];
-      let _ = reader.read_exact(&mut buf);
-      raw.push_str(&String::from_utf8_lossy(&buf));
-    } else if method == "POST" || method == "PUT" || method == "DELETE" || method == "PATCH" {
-      if has_transfer_encoding {
-        // Read all until EOF for POST/PUT/DELETE/PATCH without Content-Length
-        let mut rest = String::new();
-        let _ = stream.set_read_timeout(Some(Duration::from_millis(READ_TIMEOUT_MS)));
-        let _ = reader.take(BODY_READ_LIMIT_BYTES).read_to_string(&mut rest);
-        let _ = stream.set_read_timeout(None);
-        raw.push_str(&rest);
-      } else {
-        // No Content-Length or Transfer-Encoding; read whatever is readily available.
-        let _ = stream.set_read_timeout(Some(Duration::from_millis(READ_TIMEOUT_MS)));
-        let mut buf: Vec<u8> = Vec::new();
-        let mut chunk = [0u8; 128];
-        loop {
-          match reader.read(&mut chunk) {
-            Ok(0) => break,
-            Ok(n) => {
-              buf.extend_from_slice(&chunk[..n]);
-              if buf.len() as u64 >= BODY_READ_LIMIT_BYTES {
-                break;
-              }
-            }
-            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock || e.kind() == std::io::ErrorKind::TimedOut => {
-              break;
-            }
-            Err(_) => break,
-          }
+    let (method, content_length, chunked) = match extract_body_headers(&raw) {
+      Ok(framing) => framing,
+      Err(status) => return refused(&raw, status),
+    };
+    let head_len = raw.len();
+    let mut trailers = Vec::new();
+    let _ = stream.set_read_timeout(Some(limits.body_read_timeout));
+
+    // Chunked framing wins over Content-Length; without either, read until the body timeout.
+    if chunked {
+      let mut decoder = ChunkedDecoder::with_trailer_limits(limits.max_header_count, limits.max_header_bytes);
+      while !decoder.is_done() {
+        let available = match reader.fill_buf() {
+          Ok([]) => break,
+          Ok(available) => available,
+          Err(e) if is_timeout(&e) => return refused(&raw, StatusCode::RequestTimeout),
+          Err(_) => break,
+        };
+        // Only consume what the decoder used; the rest may be a pipelined request
+        let used = match decoder.feed(available) {
+          Ok(used) => used,
+          Err(ChunkedError::TrailersTooLarge) => return refused(&raw, StatusCode::RequestHeaderFieldsTooLarge),
+          Err(_) => break,
+        };
+        reader.consume(used);
+        if decoder.body().len() > limits.max_body_size {
+          return refused(&raw, StatusCode::PayloadTooLarge);
         }
-        let _ = stream.set_read_timeout(None);
-        if !buf.is_empty() {
-          raw.push_str(&String::from_utf8_lossy(&buf));
+      }
+      if !decoder.is_done() {
+        return refused(&raw, StatusCode::BadRequest);
+      }
+      trailers = append_chunked_body(&mut raw, decoder);
+    } else if content_length > limits.max_body_size {
+      return refused(&raw, StatusCode::PayloadTooLarge);
+    } else if content_length > 0 {
+      // Read up to content_length bytes; a body cut short by EOF is kept as-is instead of being zero-padded
+      while raw.len() - head_len < content_length {
+        let available = match reader.fill_buf() {
+          Ok([]) => break,
+          Ok(available) => available,
+          Err(e) if is_timeout(&e) => return refused(&raw, StatusCode::RequestTimeout),
+          Err(_) => break,
+        };
+        let n = available.len().min(content_length - (raw.len() - head_len));
+        raw.extend_from_slice(&available[..n]);
+        reader.consume(n);
+      }
+    } else if read_unframed_body && (method == "POST" || method == "PUT" || method == "DELETE" || method == "PATCH") {
+      // No Content-Length or Transfer-Encoding; whatever arrives before the body timeout is the body.
+      // Skipped on persistent connections, where those bytes belong to the next request.
+      while let Ok(available) = reader.fill_buf() {
+        if available.is_empty() {
+          break;
+        }
+        let n = available.len();
+        raw.extend_from_slice(available);
+        reader.consume(n);
+        if raw.len() - head_len > limits.max_body_size {
+          return refused(&raw, StatusCode::PayloadTooLarge);
         }
       }
     }
+    let _ = stream.set_read_timeout(None);
 
-    Self::parse_raw_sync(raw, routes, file_bases)
+    let (mut request, error) = Self::parse_raw(raw, limits);
+    request.trailers = trailers;
+    Some((request, error))
   }
 
-  #[cfg(feature = "sync")]
-  pub fn parse_raw_sync(
-    raw: String,
-    routes: &HashMap<(Rt, String), Rh>,
-    file_bases: &[String],
-  ) -> (Self, Option<Response>) {
-    if raw.trim().is_empty() {
-      return (
-        Self::default(),
-        Some(Response {
-          status: StatusCode::BadRequest.to_string(),
-          content_type: String::new(),
-          content: Vec::new(),
-        }),
-      );
-    }
-    let parts: Vec<&str> = raw.split_whitespace().collect();
-    if parts.len() < 3 {
-      return (
-        Self::default(),
-        Some(Response {
-          status: StatusCode::BadRequest.to_string(),
-          content_type: String::new(),
-          content: Vec::new(),
-        }),
-      );
-    }
-    let method_str = parts[0];
-    let path_str = parts[1];
-    let version = parts[2];
-    let allowed = ["GET", "POST", "PUT", "DELETE", "OPTIONS", "HEAD", "PATCH", "CONNECT", "TRACE"];
-    if !allowed.contains(&method_str) {
-      return (
-        Self::default(),
-        Some(Response {
-          status: StatusCode::MethodNotAllowed.to_string(),
-          content_type: String::new(),
-          content: Vec::new(),
-        }),
-      );
-    }
-    if version != "HTTP/1.1" {
-      return (
-        Self::default(),
-        Some(Response {
-          status: StatusCode::HttpVersionNotSupported.to_string(),
-          content_type: String::new(),
-          content: Vec::new(),
-        }),
-      );
-    }
-    const MAX_URI: usize = 2000;
-    if path_str.len() > MAX_URI {
-      return (
-        Self::default(),
-        Some(Response {
-          status: StatusCode::UriTooLong.to_string(),
-          content_type: String::new(),
-          content: Vec::new(),
-        }),
-      );
+  /// A blank request holding only the header fields of `raw`, for answering a request that
+  /// could not be parsed.
+  fn with_head_fields(raw: &[u8]) -> Self {
+    let head = String::from_utf8_lossy(split_head(raw).0);
+    let headers = head
+      .split("\r\n")
+      .skip(1)
+      .filter_map(|line| line.split_once(':'))
+      .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+      .collect();
+    Request {
+      headers,
+      ..Self::default()
     }
-    let mut req = Self::parse_raw_only(raw, routes);
-    let early = req.route_sync(routes, file_bases);
-    (req, early)
   }
 
-  #[cfg(any(feature = "async_tokio", feature = "async_std", feature = "async_smol"))]
-  pub async fn parse_raw_async(
-    raw: String,
-    routes: &HashMap<(Rt, String), Rh>,
-    file_bases: &[String],
-  ) -> (Self, Option<Response>) {
-    if raw.trim().is_empty() {
-      return (
-        Self::default(),
-        Some(Response {
-          status: StatusCode::BadRequest.to_string(),
-          content_type: String::new(),
-          content: Vec::new(),
-        }),
-      );
+  /// Validates the request line, returning the error response to send when it is unusable.
+  fn check_request_line(raw: &[u8], limits: &ServerLimits) -> Option<Response> {
+    let head = String::from_utf8_lossy(split_head(raw).0);
+    let error = |status: StatusCode| Some(error_response(status));
+    if head.trim().is_empty() {
+      return error(StatusCode::BadRequest);
     }
-    let parts: Vec<&str> = raw.split_whitespace().collect();
+    let parts: Vec<&str> = head.split_whitespace().collect();
     if parts.len() < 3 {
-      return (
-        Self::default(),
-        Some(Response {
-          status: StatusCode::BadRequest.to_string(),
-          content_type: String::new(),
-          content: Vec::new(),
-        }),
-      );
+      return error(StatusCode::BadRequest);
     }
     let method_str = parts[0];
     let path_str = parts[1];
     let version = parts[2];
     let allowed = ["GET", "POST", "PUT", "DELETE", "OPTIONS", "HEAD", "PATCH", "CONNECT", "TRACE"];
     if !allowed.contains(&method_str) {
-      return (
-        Self::default(),
-        Some(Response {
-          status: StatusCode::MethodNotAllowed.to_string(),
-          content_type: String::new(),
-          content: Vec::new(),
-        }),
-      );
+      return error(StatusCode::MethodNotAllowed);
     }
     if version != "HTTP/1.1" {
-      return (
-        Self::default(),
-        Some(Response {
-          status: StatusCode::HttpVersionNotSupported.to_string(),
-          content_type: String::new(),
-          content: Vec::new(),
-        }),
-      );
+      return error(StatusCode::HttpVersionNotSupported);
     }
-    const MAX_URI: usize = 2000;
-    if path_str.len() > MAX_URI {
-      return (
-        Self::default(),
-        Some(Response {
-          status: StatusCode::UriTooLong.to_string(),
-          content_type: String::new(),
-          content: Vec::new(),
-        }),
-      );
+    if path_str.len() > limits.max_uri_length {
+      return error(StatusCode::UriTooLong);
     }
-    let mut req = Self::parse_raw_only(raw, routes);
-    // route is async under these features, await it here
-    let early = req.route_async(routes, file_bases).await;
-    (req, early)
-  }
-
-  fn parse_raw_only(raw: String, routes: &HashMap<(Rt, String), Rh>) -> Self {
-    let lines: Vec<&str> = raw.split("\r\n").collect();
-    let mut cut = 0;
-    for (i, &l) in lines.iter().enumerate() {
-      if l.trim().is_empty() {
-        cut = i;
-        break;
-      }
+    None
+  }
+
+  /// Parses a complete raw request (head plus body).
+  /// The response is set only when the request line is unusable; routing happens afterwards.
+  pub fn parse_raw(raw: Vec<u8>, limits: &ServerLimits) -> (Self, Option<Response>) {
+    if let Some(error) = Self::check_request_line(&raw, limits) {
+      return (Self::with_head_fields(&raw), Some(error));
     }
-    let headers = lines[..cut]
-      .iter()
-      .filter_map(|&h| {
+    (Self::parse_raw_only(&raw), None)
+  }
+
+  fn parse_raw_only(raw: &[u8]) -> Self {
+    let (head, body) = split_head(raw);
+    let head = String::from_utf8_lossy(head);
+    let headers = head
+      .split("\r\n")
+      .skip(1)
+      .filter_map(|h| {
         let p: Vec<&str> = h.split(": ").collect();
         (p.len() == 2).then(|| (p[0].to_string(), p[1].to_string()))
       })
       .collect();
-    let body = lines[cut + 1..].join("\r\n");
-    let parts: Vec<&str> = raw.split_whitespace().collect();
+    let parts: Vec<&str> = head.split_whitespace().collect();
     let mut path = parts[1].to_string();
     let mut params = HashMap::new();
     let query_opt = if let Some(qpos) = path.find('?') {
@@ -618,18 +705,10 @@ impl Request {
     } else {
       None
     };
-    for (m, rp) in routes.keys() {
-      if *m == RequestType::from_str(parts[0]) {
-        for (k, v) in Self::extract_params(rp, &path) {
-          params.insert(k, v);
-        }
-        break;
-      }
-    }
     if let Some(qs) = query_opt {
       for p in qs.split('&') {
         if let Some(eq) = p.find('=') {
-          params.insert(p[..eq].to_string(), p[eq + 1..].to_string());
+          params.insert(form_decode(&p[..eq]).into_owned(), form_decode(&p[eq + 1..]).into_owned());
         }
       }
     }
@@ -638,79 +717,146 @@ impl Request {
       path,
       version: parts[2].to_string(),
       headers,
-      body,
+      body: body.to_vec(),
+      trailers: Vec::new(),
       params,
+      path_params: HashMap::new(),
+      extensions: Extensions::new(),
+      path_names: Vec::new(),
+      routes: None,
+      state: None,
     }
   }
 
-  #[cfg(feature = "sync")]
-  pub fn route_sync(&mut self, routes: &HashMap<(Rt, String), Rh>, file_bases: &[String]) -> Option<Response> {
-    if let Some(rh) = routes.get(&(self.method.clone(), self.path.clone())) {
-      return Some(futures::executor::block_on(rh.handler.handle(self)));
-    }
-    for ((m, rp), rh) in routes {
-      if *m == self.method {
-        let path_p = Self::extract_params(rp, &self.path);
-        if !path_p.is_empty() {
-          let mut merged = HashMap::new();
-          for (k, v) in path_p {
-            merged.insert(k, v);
-          }
-          for (k, v) in self.params.drain() {
-            merged.insert(k, v);
-          }
-          self.params = merged;
-          return Some(futures::executor::block_on(rh.handler.handle(self)));
-        }
-      }
+  /// Runs the matching route's handler, serves a static file for GET and HEAD, or answers
+  /// 405 for a path that only has routes for other methods (rendered through `errors`).
+  /// `None` means nothing matched.
+  pub(crate) async fn route(
+    &mut self,
+    routes: &Arc<RouteTree>,
+    file_bases: &[String],
+    errors: &ErrorPages,
+  ) -> Option<Response> {
+    self.set_routes(routes);
+    if let Some(found) = self.find_route(routes) {
+      self.path_params = found.params;
+      self.path_names = found.captures.to_vec();
+      let next = Next::new(found.middleware, &*found.handler.handler);
+      return Some(next.run(self).await);
     }
-    if self.method == Rt::GET {
-      return Some(self.serve_file(file_bases));
+    // A file wins over routes that only exist for other methods
+    if (self.method == Rt::GET || self.method == Rt::HEAD)
+      && let Some(response) = self.serve_file(file_bases)
+    {
+      return Some(response);
     }
-    None
+    self
+      .method_not_allowed(routes)
+      .map(|response| errors.render(self, response))
   }
 
+  /// Runs the matching route's handler, serves a static file for GET and HEAD, or answers
+  /// 405 for a path that only has routes for other methods (rendered through `errors`),
+  /// blocking until the handler finishes. `None` means nothing matched. The server's global
+  /// middleware is not involved.
+  #[cfg(feature = "sync")]
+  pub fn route_sync(
+    &mut self,
+    routes: &Arc<RouteTree>,
+    file_bases: &[String],
+    errors: &ErrorPages,
+  ) -> Option<Response> {
+    futures::executor::block_on(self.route(routes, file_bases, errors))
+  }
+
+  /// Runs the matching route's handler, serves a static file for GET and HEAD, or answers
+  /// 405 for a path that only has routes for other methods (rendered through `errors`).
+  /// `None` means nothing matched. The server's global middleware is not involved.
   #[cfg(any(feature = "async_tokio", feature = "async_std", feature = "async_smol"))]
-  pub async fn route_async(&mut self, routes: &HashMap<(Rt, String), Rh>, file_bases: &[String]) -> Option<Response> {
-    if let Some(rh) = routes.get(&(self.method.clone(), self.path.clone())) {
-      return Some(rh.handler.handle(self).await);
-    }
-    for ((m, rp), rh) in routes {
-      if *m == self.method {
-        let path_p = Self::extract_params(rp, &self.path);
-        if !path_p.is_empty() {
-          let mut merged = HashMap::new();
-          for (k, v) in path_p {
-            merged.insert(k, v);
-          }
-          for (k, v) in self.params.drain() {
-            merged.insert(k, v);
-          }
-          self.params = merged;
-          return Some(rh.handler.handle(self).await);
-        }
-      }
-    }
-    if self.method == Rt::GET {
-      return Some(self.serve_file(file_bases));
+  pub async fn route_async(
+    &mut self,
+    routes: &Arc<RouteTree>,
+    file_bases: &[String],
+    errors: &ErrorPages,
+  ) -> Option<Response> {
+    self.route(routes, file_bases, errors).await
+  }
+
+  /// The application state of type `T` the server was given with `with_state`. `None` when
+  /// the server has no state of that type.
+  pub fn state<T: Send + Sync + 'static>(&self) -> Option<&T> {
+    self.state.as_ref().and_then(|state| state.get::<T>())
+  }
+
+  pub(crate) fn set_state(&mut self, state: &Arc<SharedState>) {
+    self.state = Some(Arc::clone(state));
+  }
+
+  /// Points `url_for` and `is_routed` at the routes of the server handling this request.
+  pub(crate) fn set_routes(&mut self, routes: &Arc<RouteTree>) {
+    self.routes = Some(Arc::clone(routes));
+  }
+
+  /// Whether some route, for any method, matches this request's path.
+  pub(crate) fn is_routed(&self) -> bool {
+    self
+      .routes
+      .as_ref()
+      .is_some_and(|routes| !routes.allowed_methods(&self.path).is_empty())
+  }
+
+  /// Looks up the route for this request. HEAD falls back to the GET route of the same path;
+  /// the writer drops the body but keeps the headers.
+  fn find_route<'a>(&self, routes: &'a RouteTree) -> Option<RouteMatch<'a>> {
+    routes.find(&self.method, &self.path).or_else(|| match self.method {
+      Rt::HEAD => routes.find(&Rt::GET, &self.path),
+      _ => None,
+    })
+  }
+
+  /// Answers a path that has routes, just none for this method: OPTIONS gets 204 and any
+  /// other method 405, both with an `Allow` header listing the methods the path supports.
+  fn method_not_allowed(&self, routes: &RouteTree) -> Option<Response> {
+    let allowed = routes.allowed_methods(&self.path);
+    if allowed.is_empty() {
+      return None;
     }
-    None
+    let allow: Vec<String> = Rt::ALL
+      .iter()
+      .filter(|method| match method {
+        Rt::OPTIONS => true,
+        Rt::HEAD => allowed.contains(&Rt::HEAD) || allowed.contains(&Rt::GET),
+        _ => allowed.contains(method),
+      })
+      .map(Rt::to_string)
+      .collect();
+    let status = if self.method == Rt::OPTIONS {
+      StatusCode::NoContent
+    } else {
+      StatusCode::MethodNotAllowed
+    };
+    let mut response = error_response(status);
+    response.headers.push(("Allow".to_string(), allow.join(", ")));
+    Some(response)
   }
 
-  fn serve_file(&self, bases: &[String]) -> Response {
+  fn serve_file(&self, bases: &[String]) -> Option<Response> {
     for base in bases {
       let base_path = Path::new(base);
-      if let Some(real_path) = crate::core::utils::secure_path(base_path, &self.path) {
-        if let Ok(data) = std::fs::read(&real_path) {
-          return Response {
-            status: StatusCode::Ok.to_string(),
-            content_type: crate::core::utils::get_content_type_quick(&real_path),
-            content: data,
-          };
-        }
+      if let Some(real_path) = crate::core::utils::secure_path(base_path, &self.path)
+        && let Ok(data) = std::fs::read(&real_path)
+      {
+        return Some(Response {
+          status: StatusCode::Ok,
+          content_type: crate::core::utils::get_content_type_quick(&real_path),
+          content: data,
+          headers: Vec::new(),
+          stream: None,
+          extensions: Extensions::new(),
+        });
       }
     }
-    Response::new()
+    None
   }
 }
 
@@ -727,8 +873,14 @@ impl Default for Request {
       path: String::new(),
       version: String::new(),
       headers: vec![],
-      body: String::new(),
+      body: Vec::new(),
+      trailers: Vec::new(),
       params: HashMap::new(),
+      path_params: HashMap::new(),
+      extensions: Extensions::new(),
+      path_names: Vec::new(),
+      routes: None,
+      state: None,
     }
   }
 }
@@ -740,6 +892,77 @@ impl Default for Request {
   feature = "async_smol"
 ))]
 use std::fmt::{Display, Formatter};
+#[cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+use std::str::FromStr;
+
+/// Returned by `Request::param` when a path parameter is absent or does not parse.
+#[cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParamError {
+  /// The matched route has no parameter with this name.
+  Missing(String),
+  /// The captured value is not a valid value of the requested type.
+  Invalid { name: String, value: String, reason: String },
+}
+
+#[cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+impl Display for ParamError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ParamError::Missing(name) => write!(f, "missing path parameter `{}`", name),
+      ParamError::Invalid { name, value, reason } => {
+        write!(f, "invalid path parameter `{}` = {:?}: {}", name, value, reason)
+      }
+    }
+  }
+}
+
+#[cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+impl std::error::Error for ParamError {}
+
+#[cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+impl From<ParamError> for Response {
+  fn from(err: ParamError) -> Self {
+    Response::builder().status(StatusCode::BadRequest).text(err.to_string())
+  }
+}
+
+#[cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+impl IntoResponse for ParamError {
+  fn into_response(self) -> Response {
+    self.into()
+  }
+}
 
 #[cfg(any(
   feature = "sync",
@@ -749,12 +972,12 @@ use std::fmt::{Display, Formatter};
 ))]
 impl Display for Request {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-    let mut keys: Vec<&String> = self.params.keys().collect();
-    keys.sort();
-    let params_str = {
+    let sorted = |map: &HashMap<String, String>| {
+      let mut keys: Vec<&String> = map.keys().collect();
+      keys.sort();
       let parts: Vec<String> = keys
         .into_iter()
-        .map(|k| format!("\"{}\": \"{}\"", k, self.params[k]))
+        .map(|k| format!("\"{}\": \"{}\"", k, map[k]))
         .collect();
       format!("{{{}}}", parts.join(", "))
     };
@@ -765,8 +988,15 @@ impl Display for Request {
        Version: {}\n\
        Headers: {:#?},\n\
        Body: {}\n\
-       Params: {}",
-      self.method, self.path, self.version, self.headers, self.body, params_str
+       Params: {}\n\
+       Path params: {}",
+      self.method,
+      self.path,
+      self.version,
+      self.headers,
+      self.text_lossy(),
+      sorted(&self.params),
+      sorted(&self.path_params)
     )
   }
 }
@@ -774,17 +1004,19 @@ impl Display for Request {
 #[cfg(feature = "sync")]
 pub fn handle_request_sync(
   req: &mut Request,
-  routes: &HashMap<(Rt, String), Rh>,
+  routes: &Arc<RouteTree>,
   file_bases: &[String],
+  errors: &ErrorPages,
 ) -> Option<Response> {
-  req.route_sync(routes, file_bases)
+  req.route_sync(routes, file_bases, errors)
 }
 
 #[cfg(any(feature = "async_tokio", feature = "async_std", feature = "async_smol"))]
 pub async fn handle_request_async(
   req: &mut Request,
-  routes: &HashMap<(Rt, String), Rh>,
+  routes: &Arc<RouteTree>,
   file_bases: &[String],
+  errors: &ErrorPages,
 ) -> Option<Response> {
-  req.route_async(routes, file_bases).await
+  req.route_async(routes, file_bases, errors).await
 }
@@ -22,6 +22,20 @@ impl Display for RequestType {
 }
 
 impl RequestType {
+  /// Every method, in declaration order.
+  pub const ALL: [RequestType; 9] = [
+    RequestType::GET,
+    RequestType::POST,
+    RequestType::PUT,
+    RequestType::DELETE,
+    RequestType::HEAD,
+    RequestType::OPTIONS,
+    RequestType::CONNECT,
+    RequestType::PATCH,
+    RequestType::TRACE,
+  ];
+
+  #[allow(clippy::should_implement_trait)]
   pub fn from_str(s: &str) -> Self {
     match s.to_uppercase().as_str() {
       "GET" => RequestType::GET,
@@ -1,20 +1,69 @@
-use std::fmt::{Display, Formatter, Result};
+use std::fmt::{self, Debug, Display, Formatter, Result};
 
+use futures::stream::{BoxStream, Stream, StreamExt};
+
+use crate::core::extensions::Extensions;
 use crate::core::status_code::StatusCode;
 
+/// A response body produced piece by piece and sent with `Transfer-Encoding: chunked`.
+///
+/// `Iter` suits the `sync` server, `Stream` the async runtimes; every server accepts both.
+pub enum BodyStream {
+  Iter(Box<dyn Iterator<Item = Vec<u8>> + Send>),
+  Stream(BoxStream<'static, Vec<u8>>),
+}
+
+impl Debug for BodyStream {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      BodyStream::Iter(_) => write!(f, "BodyStream::Iter"),
+      BodyStream::Stream(_) => write!(f, "BodyStream::Stream"),
+    }
+  }
+}
+
+impl BodyStream {
+  /// Turns the body into a blocking iterator, driving a `Stream` on the current thread.
+  pub fn into_blocking_iter(self) -> Box<dyn Iterator<Item = Vec<u8>> + Send> {
+    match self {
+      BodyStream::Iter(iter) => iter,
+      BodyStream::Stream(stream) => Box::new(futures::executor::block_on_stream(stream)),
+    }
+  }
+
+  /// Yields the next piece of the body, or `None` once it is exhausted.
+  pub async fn next_chunk(&mut self) -> Option<Vec<u8>> {
+    match self {
+      BodyStream::Iter(iter) => iter.next(),
+      BodyStream::Stream(stream) => stream.next().await,
+    }
+  }
+}
+
 #[derive(Debug)]
 pub struct Response {
-  pub status: String,
+  pub status: StatusCode,
   pub content_type: String,
   pub content: Vec<u8>,
+  /// Extra header fields, sent in order after the ones the server writes itself. A
+  /// `Content-Type` here replaces `content_type`; `Content-Length`, `Transfer-Encoding` and
+  /// `Connection` are ignored.
+  pub headers: Vec<(String, String)>,
+  /// When set, `content` is sent as the first chunk and the stream follows.
+  pub stream: Option<BodyStream>,
+  /// Typed values for the middleware the response passes through; never sent.
+  pub extensions: Extensions,
 }
 
 impl Default for Response {
   fn default() -> Self {
     Response {
-      status: StatusCode::NotFound.to_string(),
+      status: StatusCode::NotFound,
       content_type: "text/plain".to_string(),
       content: b"404 Not Found".to_vec(),
+      headers: Vec::new(),
+      stream: None,
+      extensions: Extensions::new(),
     }
   }
 }
@@ -29,4 +78,298 @@ impl Response {
   pub fn new() -> Self {
     Self::default()
   }
+
+  /// Starts a response with status 200 OK and no body.
+  pub fn builder() -> ResponseBuilder {
+    ResponseBuilder::new()
+  }
+
+  /// Returns the first value of a header set on this response, matched case-insensitively.
+  pub fn header(&self, name: &str) -> Option<&str> {
+    self
+      .headers
+      .iter()
+      .find(|(k, _)| k.eq_ignore_ascii_case(name))
+      .map(|(_, v)| v.as_str())
+  }
+
+  /// The status line to send. A `Custom` status that cannot be sent as is (see
+  /// `StatusCode::is_valid`) is written as 500 Internal Server Error instead.
+  #[cfg(any(
+    feature = "sync",
+    feature = "async_tokio",
+    feature = "async_std",
+    feature = "async_smol"
+  ))]
+  pub(crate) fn status_line(&self) -> String {
+    let status = if self.status.is_valid() {
+      self.status
+    } else {
+      StatusCode::InternalServerError
+    };
+    format!("HTTP/1.1 {}\r\n", status)
+  }
+
+  /// The `Content-Type` line to send, taken from the last `Content-Type` in `headers` when
+  /// there is one. Left out when the value holds CR or LF like any other header field that
+  /// would break the header section.
+  #[cfg(any(
+    feature = "sync",
+    feature = "async_tokio",
+    feature = "async_std",
+    feature = "async_smol"
+  ))]
+  pub(crate) fn content_type_line(&self) -> String {
+    let content_type = self
+      .headers
+      .iter()
+      .rev()
+      .find(|(k, _)| k.eq_ignore_ascii_case("content-type"))
+      .map_or(&self.content_type, |(_, v)| v);
+    if breaks_header(content_type) {
+      return String::new();
+    }
+    format!("Content-Type: {}\r\n", content_type)
+  }
+
+  /// Renders `headers` as header lines, leaving out the framing fields the server writes itself
+  /// and anything that would break the header section (CR or LF in a name or value).
+  #[cfg(any(
+    feature = "sync",
+    feature = "async_tokio",
+    feature = "async_std",
+    feature = "async_smol"
+  ))]
+  pub(crate) fn header_lines(&self) -> String {
+    let managed = ["content-type", "content-length", "transfer-encoding", "connection"];
+    let mut lines = String::new();
+    for (k, v) in &self.headers {
+      if k.is_empty() || breaks_header(k) || breaks_header(v) || managed.contains(&k.to_ascii_lowercase().as_str()) {
+        continue;
+      }
+      lines.push_str(&format!("{}: {}\r\n", k, v));
+    }
+    lines
+  }
+
+  /// Builds a chunked response whose body is pulled from an iterator.
+  pub fn from_chunks<I>(status: StatusCode, content_type: &str, chunks: I) -> Self
+  where
+    I: IntoIterator<Item = Vec<u8>>,
+    I::IntoIter: Send + 'static,
+  {
+    Response {
+      status,
+      content_type: content_type.to_string(),
+      content: Vec::new(),
+      headers: Vec::new(),
+      stream: Some(BodyStream::Iter(Box::new(chunks.into_iter()))),
+      extensions: Extensions::new(),
+    }
+  }
+
+  /// Builds a chunked response whose body is pulled from an async `Stream`.
+  pub fn from_stream<S>(status: StatusCode, content_type: &str, stream: S) -> Self
+  where
+    S: Stream<Item = Vec<u8>> + Send + 'static,
+  {
+    Response {
+      status,
+      content_type: content_type.to_string(),
+      content: Vec::new(),
+      headers: Vec::new(),
+      stream: Some(BodyStream::Stream(stream.boxed())),
+      extensions: Extensions::new(),
+    }
+  }
+}
+
+// CR or LF in a header name or value would end the field early and start a new one.
+#[cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+fn breaks_header(text: &str) -> bool {
+  text.contains(['\r', '\n'])
+}
+
+/// Fluent constructor for `Response`, e.g.
+/// `Response::builder().status(StatusCode::Created).header("Location", "/users/42").json(&user)`.
+#[derive(Debug)]
+pub struct ResponseBuilder {
+  status: StatusCode,
+  content_type: String,
+  headers: Vec<(String, String)>,
+  extensions: Extensions,
+}
+
+impl Default for ResponseBuilder {
+  fn default() -> Self {
+    ResponseBuilder {
+      status: StatusCode::Ok,
+      content_type: String::new(),
+      headers: Vec::new(),
+      extensions: Extensions::new(),
+    }
+  }
+}
+
+impl ResponseBuilder {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn status(mut self, status: StatusCode) -> Self {
+    self.status = status;
+    self
+  }
+
+  pub fn content_type(mut self, content_type: &str) -> Self {
+    self.content_type = content_type.to_string();
+    self
+  }
+
+  /// Appends a header; repeating a name (e.g. `Set-Cookie`) sends it several times.
+  /// `Content-Type` is routed to the content type, which every body method below may override.
+  pub fn header(mut self, name: &str, value: &str) -> Self {
+    if name.eq_ignore_ascii_case("content-type") {
+      self.content_type = value.to_string();
+    } else {
+      self.headers.push((name.to_string(), value.to_string()));
+    }
+    self
+  }
+
+  /// Attaches a typed value to the response's extensions, for the middleware around the handler.
+  pub fn extension<T: Send + Sync + 'static>(mut self, value: T) -> Self {
+    self.extensions.insert(value);
+    self
+  }
+
+  /// Finishes the response with a raw body.
+  pub fn body<B: Into<Vec<u8>>>(self, body: B) -> Response {
+    Response {
+      status: self.status,
+      content_type: self.content_type,
+      content: body.into(),
+      headers: self.headers,
+      stream: None,
+      extensions: self.extensions,
+    }
+  }
+
+  /// Finishes the response with a UTF-8 body, defaulting the content type to `text/plain; charset=utf-8`.
+  pub fn text<T: Into<String>>(mut self, text: T) -> Response {
+    if self.content_type.is_empty() {
+      self.content_type = "text/plain; charset=utf-8".to_string();
+    }
+    self.body(text.into())
+  }
+
+  /// Finishes the response with `value` serialized as JSON.
+  /// A value that cannot be serialized yields 500 Internal Server Error.
+  #[cfg(feature = "json")]
+  pub fn json<T: serde::Serialize + ?Sized>(mut self, value: &T) -> Response {
+    match serde_json::to_vec(value) {
+      Ok(body) => {
+        self.content_type = "application/json".to_string();
+        self.body(body)
+      }
+      Err(_) => ResponseBuilder::new().status(StatusCode::InternalServerError).body(Vec::new()),
+    }
+  }
+
+  /// Finishes the response with a chunked body pulled from an iterator.
+  pub fn chunks<I>(self, chunks: I) -> Response
+  where
+    I: IntoIterator<Item = Vec<u8>>,
+    I::IntoIter: Send + 'static,
+  {
+    let mut response = Response::from_chunks(self.status, &self.content_type, chunks);
+    response.headers = self.headers;
+    response.extensions = self.extensions;
+    response
+  }
+
+  /// Finishes the response with a chunked body pulled from an async `Stream`.
+  pub fn stream<S>(self, stream: S) -> Response
+  where
+    S: Stream<Item = Vec<u8>> + Send + 'static,
+  {
+    let mut response = Response::from_stream(self.status, &self.content_type, stream);
+    response.headers = self.headers;
+    response.extensions = self.extensions;
+    response
+  }
+}
+
+/// Values a handler can return in place of a `Response`.
+///
+/// Text answers `200 OK` as `text/plain; charset=utf-8` and bytes as
+/// `application/octet-stream`; a bare `StatusCode` answers with an empty body, and
+/// `(StatusCode, T)` overrides the status of `T`'s response. `Result` answers with whichever
+/// side it holds, so handlers can use `?` with error types that implement `IntoResponse`,
+/// and `None` answers `404 Not Found`.
+pub trait IntoResponse {
+  fn into_response(self) -> Response;
+}
+
+impl IntoResponse for Response {
+  fn into_response(self) -> Response {
+    self
+  }
+}
+
+impl IntoResponse for &str {
+  fn into_response(self) -> Response {
+    ResponseBuilder::new().text(self)
+  }
+}
+
+impl IntoResponse for String {
+  fn into_response(self) -> Response {
+    ResponseBuilder::new().text(self)
+  }
+}
+
+impl IntoResponse for Vec<u8> {
+  fn into_response(self) -> Response {
+    ResponseBuilder::new()
+      .content_type("application/octet-stream")
+      .body(self)
+  }
+}
+
+impl IntoResponse for StatusCode {
+  fn into_response(self) -> Response {
+    ResponseBuilder::new().status(self).body(Vec::new())
+  }
+}
+
+impl<T: IntoResponse> IntoResponse for (StatusCode, T) {
+  fn into_response(self) -> Response {
+    let mut response = self.1.into_response();
+    response.status = self.0;
+    response
+  }
+}
+
+impl<T: IntoResponse, E: IntoResponse> IntoResponse for std::result::Result<T, E> {
+  fn into_response(self) -> Response {
+    match self {
+      Ok(value) => value.into_response(),
+      Err(err) => err.into_response(),
+    }
+  }
+}
+
+impl<T: IntoResponse> IntoResponse for Option<T> {
+  fn into_response(self) -> Response {
+    match self {
+      Some(value) => value.into_response(),
+      None => StatusCode::NotFound.into_response(),
+    }
+  }
 }
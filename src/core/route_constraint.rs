@@ -0,0 +1,249 @@
+use std::cmp::Ordering;
+use std::fmt::{self, Debug, Formatter};
+use std::str::FromStr;
+
+/// Check attached to a route parameter, written after a colon: `{id:u64}`, `{slug:[a-z-]+}`.
+///
+/// A constraint is either a name or a pattern:
+/// - integer types (`u8` to `u128`, `i8` to `i128`, `usize`, `isize`) accept what `str::parse`
+///   accepts for that type;
+/// - `uuid` accepts the hyphenated form, e.g. `67e55044-10b1-426f-9247-bb680e5fe0c8`;
+/// - `alpha` and `alnum` accept one or more ASCII letters, or letters and digits;
+/// - anything else is a pattern that must match the whole value. Patterns are a small subset of
+///   regular expressions: literal characters, `.`, `\d`, `\w`, classes like `[a-z-]` or `[^/]`,
+///   each optionally followed by `?`, `*`, `+` or a repetition count, `{2}`, `{2,}` or `{2,4}`.
+#[derive(Clone)]
+pub struct Constraint {
+  source: String,
+  check: Check,
+}
+
+#[derive(Clone)]
+enum Check {
+  Parse(fn(&str) -> bool),
+  Uuid,
+  Pattern(Vec<Piece>),
+}
+
+#[derive(Clone)]
+struct Piece {
+  atom: Atom,
+  min: usize,
+  max: Option<usize>,
+}
+
+#[derive(Clone)]
+enum Atom {
+  Any,
+  Char(char),
+  Class { negated: bool, ranges: Vec<(char, char)> },
+}
+
+fn parses<T: FromStr>(value: &str) -> bool {
+  value.parse::<T>().is_ok()
+}
+
+fn is_uuid(value: &str) -> bool {
+  value.len() == 36
+    && value.char_indices().all(|(i, c)| match i {
+      8 | 13 | 18 | 23 => c == '-',
+      _ => c.is_ascii_hexdigit(),
+    })
+}
+
+impl Atom {
+  fn matches(&self, c: char) -> bool {
+    match self {
+      Atom::Any => true,
+      Atom::Char(expected) => c == *expected,
+      Atom::Class { negated, ranges } => ranges.iter().any(|(lo, hi)| (*lo..=*hi).contains(&c)) != *negated,
+    }
+  }
+}
+
+fn word_ranges() -> Vec<(char, char)> {
+  vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')]
+}
+
+fn escaped(c: char) -> Atom {
+  match c {
+    'd' => Atom::Class {
+      negated: false,
+      ranges: vec![('0', '9')],
+    },
+    'w' => Atom::Class {
+      negated: false,
+      ranges: word_ranges(),
+    },
+    other => Atom::Char(other),
+  }
+}
+
+fn parse_class(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> Result<Atom, String> {
+  let negated = chars.next_if_eq(&'^').is_some();
+  let mut ranges = Vec::new();
+  loop {
+    let lo = match chars.next() {
+      None => return Err("unclosed `[` in pattern".to_string()),
+      Some(']') if !ranges.is_empty() => break,
+      Some('\\') => chars.next().ok_or("pattern ends with `\\`")?,
+      Some(c) => c,
+    };
+    // `-` right before `]` is a literal, not a range
+    let mut ahead = chars.clone();
+    let hi = match (ahead.next(), ahead.next()) {
+      (Some('-'), Some(end)) if end != ']' => {
+        chars.nth(1);
+        end
+      }
+      _ => lo,
+    };
+    if hi < lo {
+      return Err(format!("invalid range `{}-{}` in pattern", lo, hi));
+    }
+    ranges.push((lo, hi));
+  }
+  Ok(Atom::Class { negated, ranges })
+}
+
+/// Reads a `{n}`, `{n,}` or `{n,m}` repetition count whose `{` was just taken.
+fn parse_repetition(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> Result<(usize, Option<usize>), String> {
+  let mut body = String::new();
+  loop {
+    match chars.next() {
+      None => return Err("unclosed `{` in pattern".to_string()),
+      Some('}') => break,
+      Some(c) => body.push(c),
+    }
+  }
+  let invalid = || format!("invalid repetition `{{{}}}` in pattern", body);
+  let count = |text: &str| text.parse::<usize>().map_err(|_| invalid());
+  let (min, max) = match body.split_once(',') {
+    None => (count(&body)?, Some(count(&body)?)),
+    Some((min, "")) => (count(min)?, None),
+    Some((min, max)) => (count(min)?, Some(count(max)?)),
+  };
+  if max.is_some_and(|max| max < min) {
+    return Err(invalid());
+  }
+  Ok((min, max))
+}
+
+fn parse_pattern(source: &str) -> Result<Vec<Piece>, String> {
+  let mut pieces: Vec<Piece> = Vec::new();
+  let mut chars = source.chars().peekable();
+  while let Some(c) = chars.next() {
+    let atom = match c {
+      '[' => parse_class(&mut chars)?,
+      '.' => Atom::Any,
+      '\\' => escaped(chars.next().ok_or("pattern ends with `\\`")?),
+      '?' | '*' | '+' | '{' => return Err(format!("`{}` does not follow anything in pattern", c)),
+      '}' => return Err("unmatched `}` in pattern".to_string()),
+      other => Atom::Char(other),
+    };
+    let (min, max) = match chars.next_if(|c| matches!(c, '?' | '*' | '+' | '{')) {
+      Some('?') => (0, Some(1)),
+      Some('*') => (0, None),
+      Some('+') => (1, None),
+      Some(_) => parse_repetition(&mut chars)?,
+      None => (1, Some(1)),
+    };
+    pieces.push(Piece { atom, min, max });
+  }
+  Ok(pieces)
+}
+
+/// Matches `chars` against `pieces`, letting each repetition take as much as it can first.
+fn match_pieces(pieces: &[Piece], chars: &[char]) -> bool {
+  let Some((piece, rest)) = pieces.split_first() else {
+    return chars.is_empty();
+  };
+  let mut taken = 0;
+  while piece.max.is_none_or(|max| taken < max) && taken < chars.len() && piece.atom.matches(chars[taken]) {
+    taken += 1;
+  }
+  loop {
+    if taken >= piece.min && match_pieces(rest, &chars[taken..]) {
+      return true;
+    }
+    if taken == 0 {
+      return false;
+    }
+    taken -= 1;
+  }
+}
+
+impl Constraint {
+  /// Parses the text after the colon of a `{name:constraint}` placeholder.
+  pub fn parse(source: &str) -> Result<Self, String> {
+    let check = match source {
+      "u8" => Check::Parse(parses::<u8>),
+      "u16" => Check::Parse(parses::<u16>),
+      "u32" => Check::Parse(parses::<u32>),
+      "u64" => Check::Parse(parses::<u64>),
+      "u128" => Check::Parse(parses::<u128>),
+      "usize" => Check::Parse(parses::<usize>),
+      "i8" => Check::Parse(parses::<i8>),
+      "i16" => Check::Parse(parses::<i16>),
+      "i32" => Check::Parse(parses::<i32>),
+      "i64" => Check::Parse(parses::<i64>),
+      "i128" => Check::Parse(parses::<i128>),
+      "isize" => Check::Parse(parses::<isize>),
+      "uuid" => Check::Uuid,
+      "alpha" => Check::Parse(|value| !value.is_empty() && value.chars().all(|c| c.is_ascii_alphabetic())),
+      "alnum" => Check::Parse(|value| !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric())),
+      "" => return Err("empty constraint".to_string()),
+      name if name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') => {
+        return Err(format!("unknown constraint `{}`", name));
+      }
+      pattern => Check::Pattern(parse_pattern(pattern)?),
+    };
+    Ok(Constraint {
+      source: source.to_string(),
+      check,
+    })
+  }
+
+  /// Whether `value` satisfies the constraint.
+  pub fn accepts(&self, value: &str) -> bool {
+    match &self.check {
+      Check::Parse(check) => check(value),
+      Check::Uuid => is_uuid(value),
+      Check::Pattern(pieces) => {
+        let chars: Vec<char> = value.chars().collect();
+        match_pieces(pieces, &chars)
+      }
+    }
+  }
+
+  /// The constraint as written in the route pattern.
+  pub fn as_str(&self) -> &str {
+    &self.source
+  }
+}
+
+impl Debug for Constraint {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    f.debug_tuple("Constraint").field(&self.source).finish()
+  }
+}
+
+impl PartialEq for Constraint {
+  fn eq(&self, other: &Self) -> bool {
+    self.source == other.source
+  }
+}
+
+impl Eq for Constraint {}
+
+impl PartialOrd for Constraint {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for Constraint {
+  fn cmp(&self, other: &Self) -> Ordering {
+    self.source.cmp(&other.source)
+  }
+}
@@ -0,0 +1,841 @@
+#![cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+
+use crate::core::cors::CorsPolicy;
+use crate::core::handler::Handler;
+use crate::core::middleware::Middleware;
+use crate::core::request_handler::Rh;
+use crate::core::request_type::Rt;
+use crate::core::route_constraint::Constraint;
+use crate::core::utils::percent_decode;
+use crate::{Request, Response};
+use async_trait::async_trait;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Debug, Display, Formatter};
+use std::sync::Arc;
+
+/// A registered route: the pattern it was added with, its handler, the middleware it runs
+/// through and the names of its captures in the order they appear in the pattern.
+#[derive(Clone)]
+pub struct RouteEntry {
+  pub pattern: String,
+  pub handler: Rh,
+  pub middleware: Vec<Arc<dyn Middleware>>,
+  captures: Vec<String>,
+}
+
+impl Debug for RouteEntry {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    f.debug_struct("RouteEntry")
+      .field("pattern", &self.pattern)
+      .field("handler", &self.handler)
+      .field("middleware", &self.middleware.len())
+      .field("captures", &self.captures)
+      .finish()
+  }
+}
+
+/// Result of a successful lookup.
+pub struct RouteMatch<'a> {
+  pub pattern: &'a str,
+  pub handler: &'a Rh,
+  pub middleware: &'a [Arc<dyn Middleware>],
+  pub params: HashMap<String, String>,
+  /// The names of `params` in the order the pattern captures them.
+  pub captures: &'a [String],
+}
+
+#[derive(Clone, Debug, Default)]
+struct Node {
+  statics: HashMap<String, Node>,
+  mixed: Vec<(Vec<Part>, Node)>,
+  params: Vec<(Option<Constraint>, Node)>,
+  catch_alls: Vec<(Option<Constraint>, HashMap<Rt, RouteEntry>)>,
+  routes: HashMap<Rt, RouteEntry>,
+}
+
+/// Piece of a segment that mixes literals and parameters, such as `{name}.{ext}`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Part {
+  Literal(String),
+  Param(Option<Constraint>),
+}
+
+/// Prefix tree over path segments that maps `(method, path)` to a route.
+///
+/// Each segment of a pattern is a literal (`users`), a parameter (`{id}`, one non-empty segment),
+/// a mix of both (`{name}.{ext}`, `{id}.json`) or, in last position only, a catch-all (`{*rest}`,
+/// one or more segments). Parameters and catch-alls may carry a [`Constraint`] (`{id:u64}`,
+/// `{slug:[a-z-]+}`) that a value must satisfy to match. Trailing parameters and catch-alls
+/// marked with `?` (`{page?}`, `{page?:u32}`, `{*path?}`) may be left out of the path.
+///
+/// Lookup walks the path once, trying literals before mixed segments before parameters before
+/// catch-alls, constrained parameters before unconstrained ones, and backing up only when a
+/// branch has no route for the method, so the winner never depends on registration order.
+///
+/// Two routes for the same method whose patterns match exactly the same paths, such as
+/// `/a/{x}` and `/a/{y}`, cannot both be added; see [`RouteError`].
+#[derive(Clone, Debug, Default)]
+pub struct RouteTree {
+  root: Node,
+  table: Vec<RouteInfo>,
+  names: HashMap<String, usize>,
+}
+
+/// A registered route as listed by `routes()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RouteInfo {
+  pub method: Rt,
+  pub pattern: String,
+  pub name: Option<String>,
+}
+
+impl Display for RouteInfo {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    write!(f, "{} {}", self.method, self.pattern)?;
+    if let Some(name) = &self.name {
+      write!(f, " ({})", name)?;
+    }
+    Ok(())
+  }
+}
+
+/// Returned when a route cannot be registered.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RouteError {
+  /// A placeholder in the pattern is malformed, e.g. it has an unknown constraint.
+  InvalidPattern { pattern: String, reason: String },
+  /// A route for the same method already matches exactly the same paths.
+  Conflict { method: Rt, pattern: String, existing: String },
+  /// Another route already goes by this name.
+  DuplicateName { name: String, existing: String },
+}
+
+impl Display for RouteError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      RouteError::InvalidPattern { pattern, reason } => {
+        write!(f, "invalid route pattern `{}`: {}", pattern, reason)
+      }
+      RouteError::Conflict { method, pattern, existing } if pattern == existing => {
+        write!(f, "duplicate route {} {}", method, pattern)
+      }
+      RouteError::Conflict { method, pattern, existing } => write!(
+        f,
+        "route {} {} conflicts with {} {}: both match the same paths",
+        method, pattern, method, existing
+      ),
+      RouteError::DuplicateName { name, existing } => {
+        write!(f, "route name `{}` is already used by {}", name, existing)
+      }
+    }
+  }
+}
+
+impl std::error::Error for RouteError {}
+
+/// Lets `Server::new` report a route list it cannot build as an `InvalidInput` error.
+impl From<RouteError> for std::io::Error {
+  fn from(err: RouteError) -> Self {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, err)
+  }
+}
+
+/// Returned by `url_for` when a URL cannot be built.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UrlError {
+  /// No route was registered under this name.
+  UnknownRoute(String),
+  /// The route's pattern has a required parameter that was not given.
+  MissingParam { route: String, param: String },
+  /// A value does not satisfy the constraint of its parameter.
+  InvalidParam { route: String, param: String, value: String },
+}
+
+impl Display for UrlError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      UrlError::UnknownRoute(route) => write!(f, "no route named `{}`", route),
+      UrlError::MissingParam { route, param } => {
+        write!(f, "route `{}` needs a value for `{}`", route, param)
+      }
+      UrlError::InvalidParam { route, param, value } => {
+        write!(f, "route `{}` does not accept {:?} for `{}`", route, value, param)
+      }
+    }
+  }
+}
+
+impl std::error::Error for UrlError {}
+
+#[derive(Clone)]
+enum Segment<'a> {
+  Static(&'a str),
+  Mixed(Vec<Part>, Vec<&'a str>),
+  Param(&'a str, Option<Constraint>),
+  CatchAll(&'a str, Option<Constraint>),
+}
+
+impl<'a> Segment<'a> {
+  /// The parameter names the segment captures, in order.
+  fn names(&self) -> Vec<&'a str> {
+    match self {
+      Segment::Static(_) => Vec::new(),
+      Segment::Mixed(_, names) => names.clone(),
+      Segment::Param(name, _) | Segment::CatchAll(name, _) => vec![*name],
+    }
+  }
+}
+
+/// Contents of a `{...}` placeholder: `[*]name[?][:constraint]`.
+struct Placeholder<'a> {
+  name: &'a str,
+  catch_all: bool,
+  optional: bool,
+  constraint: Option<Constraint>,
+}
+
+/// Joins a mount prefix and a route path into one pattern with a single leading `/`.
+fn join_path(prefix: &str, path: &str) -> String {
+  let parts = [prefix.trim_matches('/'), path.trim_start_matches('/')];
+  let joined: Vec<&str> = parts.into_iter().filter(|part| !part.is_empty()).collect();
+  format!("/{}", joined.join("/"))
+}
+
+/// Percent-encodes a value for a path segment, leaving unreserved characters (RFC 3986) as
+/// they are and `/` too when the value fills a catch-all.
+fn encode_path_value(value: &str, keep_slash: bool) -> String {
+  let mut encoded = String::with_capacity(value.len());
+  for byte in value.bytes() {
+    match byte {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+      b'/' if keep_slash => encoded.push('/'),
+      _ => encoded.push_str(&format!("%{:02X}", byte)),
+    }
+  }
+  encoded
+}
+
+fn split_path(path: &str) -> Vec<&str> {
+  let trimmed = path.strip_prefix('/').unwrap_or(path);
+  if trimmed.is_empty() {
+    Vec::new()
+  } else {
+    trimmed.split('/').collect()
+  }
+}
+
+fn parse_placeholder(inner: &str) -> Result<Placeholder<'_>, String> {
+  let (name, constraint) = match inner.split_once(':') {
+    Some((name, source)) => (name, Some(Constraint::parse(source)?)),
+    None => (inner, None),
+  };
+  let (name, catch_all) = match name.strip_prefix('*') {
+    Some(name) => (name, true),
+    None => (name, false),
+  };
+  let (name, optional) = match name.strip_suffix('?') {
+    Some(name) => (name, true),
+    None => (name, false),
+  };
+  if name.is_empty() {
+    return Err("empty parameter name".to_string());
+  }
+  Ok(Placeholder {
+    name,
+    catch_all,
+    optional,
+    constraint,
+  })
+}
+
+/// The index of the `}` closing the `{` that starts `text`, past the `{n}` repetitions,
+/// classes and escaped characters of a constraint.
+fn closing_brace(text: &str) -> Option<usize> {
+  let mut depth = 0;
+  let mut escaped = false;
+  let mut in_class = false;
+  for (i, c) in text.char_indices() {
+    match c {
+      _ if escaped => escaped = false,
+      '\\' => escaped = true,
+      _ if in_class => in_class = c != ']',
+      '[' => in_class = true,
+      '{' => depth += 1,
+      '}' => {
+        depth -= 1;
+        if depth == 0 {
+          return Some(i);
+        }
+      }
+      _ => {}
+    }
+  }
+  None
+}
+
+/// Splits a segment into literal text and `{...}` placeholders. A `{` without a closing
+/// brace is kept as literal text.
+fn parse_parts(segment: &str) -> Result<(Vec<Part>, Vec<Placeholder<'_>>), String> {
+  let mut parts = Vec::new();
+  let mut placeholders = Vec::new();
+  let mut rest = segment;
+  while let Some(open) = rest.find('{')
+    && let Some(close) = closing_brace(&rest[open..])
+  {
+    if open > 0 {
+      parts.push(Part::Literal(rest[..open].to_string()));
+    }
+    let placeholder = parse_placeholder(&rest[open + 1..open + close])?;
+    parts.push(Part::Param(placeholder.constraint.clone()));
+    placeholders.push(placeholder);
+    rest = &rest[open + close + 1..];
+  }
+  if !rest.is_empty() {
+    parts.push(Part::Literal(rest.to_string()));
+  }
+  Ok((parts, placeholders))
+}
+
+/// Classifies a pattern segment and tells whether it was marked optional.
+fn classify(segment: &str) -> Result<(Segment<'_>, bool), String> {
+  let (parts, mut placeholders) = parse_parts(segment)?;
+  if let [Part::Param(_)] = parts.as_slice() {
+    let placeholder = placeholders.pop().unwrap();
+    let optional = placeholder.optional;
+    return Ok(match placeholder.catch_all {
+      true => (Segment::CatchAll(placeholder.name, placeholder.constraint), optional),
+      false => (Segment::Param(placeholder.name, placeholder.constraint), optional),
+    });
+  }
+  if placeholders.is_empty() {
+    return Ok((Segment::Static(segment), false));
+  }
+  let names = placeholders.iter().map(|placeholder| placeholder.name).collect();
+  Ok((Segment::Mixed(parts, names), false))
+}
+
+fn accepts(constraint: &Option<Constraint>, value: &str) -> bool {
+  constraint.as_ref().is_none_or(|constraint| constraint.accepts(value))
+}
+
+/// Matches `text` against mixed-segment `parts`, pushing one value per parameter. Parameters
+/// are non-empty and take as much as they can, so `{name}.{ext}` splits `a.tar.gz` into
+/// `a.tar` and `gz`.
+fn match_parts(parts: &[Part], text: &str, values: &mut Vec<String>) -> bool {
+  let Some((head, rest)) = parts.split_first() else {
+    return text.is_empty();
+  };
+  match head {
+    Part::Literal(literal) => text
+      .strip_prefix(literal.as_str())
+      .is_some_and(|tail| match_parts(rest, tail, values)),
+    Part::Param(constraint) => {
+      let ends: Vec<usize> = text.char_indices().skip(1).map(|(i, _)| i).chain([text.len()]).collect();
+      for end in ends.into_iter().rev().filter(|&end| end > 0) {
+        if !accepts(constraint, &text[..end]) {
+          continue;
+        }
+        values.push(text[..end].to_string());
+        if match_parts(rest, &text[end..], values) {
+          return true;
+        }
+        values.pop();
+      }
+      false
+    }
+  }
+}
+
+/// Literal characters a mixed segment pins down; templates with more are tried first.
+fn literal_len(parts: &[Part]) -> usize {
+  parts
+    .iter()
+    .map(|part| match part {
+      Part::Literal(literal) => literal.len(),
+      Part::Param(_) => 0,
+    })
+    .sum()
+}
+
+fn mixed_order(a: &Vec<Part>, b: &Vec<Part>) -> Ordering {
+  literal_len(b).cmp(&literal_len(a)).then_with(|| a.cmp(b))
+}
+
+fn constraint_order(a: &Option<Constraint>, b: &Option<Constraint>) -> Ordering {
+  a.is_none().cmp(&b.is_none()).then_with(|| a.cmp(b))
+}
+
+/// Returns the value stored under `key`, inserting a default one at its place in `order`.
+fn slot<'a, K: Clone + PartialEq, V: Default>(
+  list: &'a mut Vec<(K, V)>,
+  key: &K,
+  order: fn(&K, &K) -> Ordering,
+) -> &'a mut V {
+  let index = match list.iter().position(|(existing, _)| existing == key) {
+    Some(index) => index,
+    None => {
+      let index = list.partition_point(|(existing, _)| order(existing, key) == Ordering::Less);
+      list.insert(index, (key.clone(), V::default()));
+      index
+    }
+  };
+  &mut list[index].1
+}
+
+impl RouteTree {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Adds a route. A catch-all segment that is not the last one is treated as a plain
+  /// parameter, and an optional segment followed by a required one as a required one.
+  ///
+  /// Panics when `try_insert` would fail.
+  pub fn insert(&mut self, method: Rt, pattern: &str, handler: Rh) {
+    self
+      .try_insert(method, pattern, handler)
+      .unwrap_or_else(|err| panic!("{}", err));
+  }
+
+  /// Adds a route, failing when a constraint in `pattern` is unknown or malformed or when a
+  /// route for `method` already matches the same paths.
+  pub fn try_insert(&mut self, method: Rt, pattern: &str, handler: Rh) -> Result<(), RouteError> {
+    self.try_insert_with(method, pattern, handler, Vec::new())
+  }
+
+  /// Like `try_insert`, with middleware the handler runs through, outermost first.
+  pub fn try_insert_with(
+    &mut self,
+    method: Rt,
+    pattern: &str,
+    handler: Rh,
+    middleware: Vec<Arc<dyn Middleware>>,
+  ) -> Result<(), RouteError> {
+    let classified = split_path(pattern)
+      .into_iter()
+      .map(classify)
+      .collect::<Result<Vec<_>, _>>()
+      .map_err(|reason| RouteError::InvalidPattern {
+        pattern: pattern.to_string(),
+        reason,
+      })?;
+    let optional = classified.iter().rev().take_while(|(_, optional)| *optional).count();
+    let segments: Vec<Segment> = classified.into_iter().map(|(segment, _)| segment).collect();
+    let mut names = HashSet::new();
+    let repeated = segments
+      .iter()
+      .flat_map(Segment::names)
+      .find(|name| !names.insert(*name));
+    if let Some(name) = repeated {
+      return Err(RouteError::InvalidPattern {
+        pattern: pattern.to_string(),
+        reason: format!("parameter `{}` appears more than once", name),
+      });
+    }
+    let lengths = segments.len() - optional..=segments.len();
+    // Check every variant first so a conflict leaves the tree untouched
+    for len in lengths.clone() {
+      if let Some(existing) = self.registered(&method, &segments[..len]) {
+        return Err(RouteError::Conflict {
+          method,
+          pattern: pattern.to_string(),
+          existing: existing.pattern.clone(),
+        });
+      }
+    }
+    for len in lengths {
+      let entry = RouteEntry {
+        pattern: pattern.to_string(),
+        handler: handler.clone(),
+        middleware: middleware.clone(),
+        captures: Vec::new(),
+      };
+      self.insert_segments(method.clone(), &segments[..len], entry);
+    }
+    self.table.push(RouteInfo {
+      method,
+      pattern: pattern.to_string(),
+      name: None,
+    });
+    Ok(())
+  }
+
+  /// Builds a tree from `((method, pattern), handler)` pairs, failing on the first route
+  /// `try_insert` refuses.
+  pub fn try_from_routes<I: IntoIterator<Item = ((Rt, String), Rh)>>(routes: I) -> Result<Self, RouteError> {
+    let mut tree = RouteTree::new();
+    for ((method, pattern), handler) in routes {
+      tree.try_insert(method, &pattern, handler)?;
+    }
+    Ok(tree)
+  }
+
+  /// Like `try_insert_with`, also naming the route for `url_for`. Nothing is added when the
+  /// name is already taken.
+  pub fn try_insert_named(
+    &mut self,
+    name: &str,
+    method: Rt,
+    pattern: &str,
+    handler: Rh,
+    middleware: Vec<Arc<dyn Middleware>>,
+  ) -> Result<(), RouteError> {
+    self.name_unused(name)?;
+    self.try_insert_with(method.clone(), pattern, handler, middleware)?;
+    self.try_name(name, &method, pattern)
+  }
+
+  fn name_unused(&self, name: &str) -> Result<(), RouteError> {
+    match self.names.get(name) {
+      Some(&index) => Err(RouteError::DuplicateName {
+        name: name.to_string(),
+        existing: format!("{} {}", self.table[index].method, self.table[index].pattern),
+      }),
+      None => Ok(()),
+    }
+  }
+
+  /// Gives the route just added for `method` and `pattern` a name for `url_for`.
+  fn try_name(&mut self, name: &str, method: &Rt, pattern: &str) -> Result<(), RouteError> {
+    self.name_unused(name)?;
+    let index = self
+      .table
+      .iter()
+      .rposition(|route| route.method == *method && route.pattern == pattern)
+      .expect("named routes are added first");
+    self.table[index].name = Some(name.to_string());
+    self.names.insert(name.to_string(), index);
+    Ok(())
+  }
+
+  /// Every route in the order it was added, including the preflight routes of CORS groups.
+  pub fn routes(&self) -> &[RouteInfo] {
+    &self.table
+  }
+
+  /// Builds the path of the route registered as `name`, filling its placeholders from
+  /// `params` and percent-encoding the values. Catch-all values keep their `/` separators;
+  /// optional trailing segments without a value are left out.
+  pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Result<String, UrlError> {
+    let pattern = match self.names.get(name) {
+      Some(&index) => &self.table[index].pattern,
+      None => return Err(UrlError::UnknownRoute(name.to_string())),
+    };
+    let value_of = |param: &str| params.iter().find(|(k, _)| *k == param).map(|(_, v)| *v);
+    let mut url = String::new();
+    let mut skipped: Option<&str> = None;
+    for segment in split_path(pattern) {
+      let (parts, placeholders) = parse_parts(segment).expect("pattern was checked when the route was added");
+      let mut built = String::new();
+      let mut placeholders = placeholders.into_iter();
+      for part in &parts {
+        let constraint = match part {
+          Part::Literal(text) => {
+            built.push_str(text);
+            continue;
+          }
+          Part::Param(constraint) => constraint,
+        };
+        let placeholder = placeholders.next().unwrap();
+        let Some(value) = value_of(placeholder.name) else {
+          if placeholder.optional && parts.len() == 1 {
+            skipped.get_or_insert(placeholder.name);
+            continue;
+          }
+          return Err(UrlError::MissingParam {
+            route: name.to_string(),
+            param: placeholder.name.to_string(),
+          });
+        };
+        if value.is_empty() || !accepts(constraint, value) {
+          return Err(UrlError::InvalidParam {
+            route: name.to_string(),
+            param: placeholder.name.to_string(),
+            value: value.to_string(),
+          });
+        }
+        built.push_str(&encode_path_value(value, placeholder.catch_all && parts.len() == 1));
+      }
+      if built.is_empty() && skipped.is_some() {
+        continue;
+      }
+      // an optional segment can only be left out when nothing follows it
+      if let Some(param) = skipped {
+        return Err(UrlError::MissingParam {
+          route: name.to_string(),
+          param: param.to_string(),
+        });
+      }
+      url.push('/');
+      url.push_str(&built);
+    }
+    if url.is_empty() {
+      url.push('/');
+    }
+    Ok(url)
+  }
+
+  /// Adds every route of `router` under `prefix`, e.g. `/api/v1`.
+  ///
+  /// Panics when `try_mount` would fail.
+  pub fn mount(&mut self, prefix: &str, router: Router) {
+    self.try_mount(prefix, router).unwrap_or_else(|err| panic!("{}", err));
+  }
+
+  /// Adds every route of `router` under `prefix`, failing when one of them cannot be added;
+  /// see `try_insert`. Route names must be unused as well. On failure none of the routes of
+  /// `router` are added.
+  pub fn try_mount(&mut self, prefix: &str, router: Router) -> Result<(), RouteError> {
+    let mut tree = self.clone();
+    tree.mount_within(prefix, router, &[])?;
+    *self = tree;
+    Ok(())
+  }
+
+  fn mount_within(&mut self, prefix: &str, router: Router, outer: &[Arc<dyn Middleware>]) -> Result<(), RouteError> {
+    let mut chain = outer.to_vec();
+    if let Some(policy) = &router.cors {
+      chain.push(Arc::new(policy.clone()));
+    }
+    chain.extend(router.middleware.iter().cloned());
+
+    let mut patterns = Vec::new();
+    let mut with_options = HashSet::new();
+    for (method, path, handler, name) in router.routes {
+      let pattern = join_path(prefix, &path);
+      if method == Rt::OPTIONS {
+        with_options.insert(pattern.clone());
+      }
+      if !patterns.contains(&pattern) {
+        patterns.push(pattern.clone());
+      }
+      self.try_insert_with(method.clone(), &pattern, handler, chain.clone())?;
+      if let Some(name) = name {
+        self.try_name(&name, &method, &pattern)?;
+      }
+    }
+    // A CORS group answers preflight requests for its paths unless it routes OPTIONS itself
+    if let Some(policy) = router.cors {
+      let preflight = Rh {
+        handler: Arc::new(Preflight(policy)),
+      };
+      for pattern in patterns.iter().filter(|pattern| !with_options.contains(*pattern)) {
+        // Another pattern matching the same paths may already answer OPTIONS
+        match self.try_insert_with(Rt::OPTIONS, pattern, preflight.clone(), chain.clone()) {
+          Ok(()) | Err(RouteError::Conflict { .. }) => {}
+          Err(err) => return Err(err),
+        }
+      }
+    }
+    for (path, group) in router.groups {
+      self.mount_within(&join_path(prefix, &path), group, &chain)?;
+    }
+    Ok(())
+  }
+
+  fn insert_segments(&mut self, method: Rt, segments: &[Segment], mut entry: RouteEntry) {
+    let mut captures = Vec::new();
+    let mut node = &mut self.root;
+    for (i, segment) in segments.iter().enumerate() {
+      match segment {
+        Segment::CatchAll(name, constraint) if i + 1 == segments.len() => {
+          captures.push(name.to_string());
+          entry.captures = captures;
+          slot(&mut node.catch_alls, constraint, constraint_order).insert(method, entry);
+          return;
+        }
+        Segment::CatchAll(name, constraint) | Segment::Param(name, constraint) => {
+          captures.push(name.to_string());
+          node = slot(&mut node.params, constraint, constraint_order);
+        }
+        Segment::Mixed(parts, names) => {
+          captures.extend(names.iter().map(|name| name.to_string()));
+          node = slot(&mut node.mixed, parts, mixed_order);
+        }
+        Segment::Static(text) => {
+          node = node.statics.entry(text.to_string()).or_default();
+        }
+      }
+    }
+    entry.captures = captures;
+    node.routes.insert(method, entry);
+  }
+
+  /// The route for `method` added at exactly the place `segments` lead to, if any.
+  fn registered(&self, method: &Rt, segments: &[Segment]) -> Option<&RouteEntry> {
+    let mut node = &self.root;
+    for (i, segment) in segments.iter().enumerate() {
+      match segment {
+        Segment::CatchAll(_, constraint) if i + 1 == segments.len() => {
+          let (_, routes) = node.catch_alls.iter().find(|(key, _)| key == constraint)?;
+          return routes.get(method);
+        }
+        Segment::CatchAll(_, constraint) | Segment::Param(_, constraint) => {
+          node = &node.params.iter().find(|(key, _)| key == constraint)?.1;
+        }
+        Segment::Mixed(parts, _) => {
+          node = &node.mixed.iter().find(|(key, _)| key == parts)?.1;
+        }
+        Segment::Static(text) => {
+          node = node.statics.get(*text)?;
+        }
+      }
+    }
+    node.routes.get(method)
+  }
+
+  /// Finds the route for `method` and `path` (without query string) and extracts its captures.
+  /// Each segment is percent-decoded before it is matched, so captures hold decoded values.
+  pub fn find(&self, method: &Rt, path: &str) -> Option<RouteMatch<'_>> {
+    // Segments are decoded one by one, so an encoded `/` never splits a segment
+    let decoded: Vec<Cow<str>> = split_path(path).into_iter().map(percent_decode).collect();
+    let segments: Vec<&str> = decoded.iter().map(|segment| segment.as_ref()).collect();
+    let mut values = Vec::new();
+    let entry = Self::find_in(&self.root, &segments, method, &mut values)?;
+    let params = entry.captures.iter().cloned().zip(values).collect();
+    Some(RouteMatch {
+      pattern: &entry.pattern,
+      handler: &entry.handler,
+      middleware: &entry.middleware,
+      params,
+      captures: &entry.captures,
+    })
+  }
+
+  /// Methods that have a route matching `path`, in `Rt::ALL` order.
+  pub fn allowed_methods(&self, path: &str) -> Vec<Rt> {
+    Rt::ALL
+      .into_iter()
+      .filter(|method| self.find(method, path).is_some())
+      .collect()
+  }
+
+  fn find_in<'a>(node: &'a Node, segments: &[&str], method: &Rt, values: &mut Vec<String>) -> Option<&'a RouteEntry> {
+    let Some((head, rest)) = segments.split_first() else {
+      return node.routes.get(method);
+    };
+    if let Some(child) = node.statics.get(*head)
+      && let Some(entry) = Self::find_in(child, rest, method, values)
+    {
+      return Some(entry);
+    }
+    for (parts, child) in &node.mixed {
+      let mark = values.len();
+      if match_parts(parts, head, values)
+        && let Some(entry) = Self::find_in(child, rest, method, values)
+      {
+        return Some(entry);
+      }
+      values.truncate(mark);
+    }
+    for (constraint, child) in &node.params {
+      if head.is_empty() || !accepts(constraint, head) {
+        continue;
+      }
+      values.push(head.to_string());
+      if let Some(entry) = Self::find_in(child, rest, method, values) {
+        return Some(entry);
+      }
+      values.pop();
+    }
+    if node.catch_alls.is_empty() {
+      return None;
+    }
+    let remainder = segments.join("/");
+    if remainder.is_empty() {
+      return None;
+    }
+    for (constraint, routes) in &node.catch_alls {
+      if accepts(constraint, &remainder)
+        && let Some(entry) = routes.get(method)
+      {
+        values.push(remainder);
+        return Some(entry);
+      }
+    }
+    None
+  }
+}
+
+/// Routes built apart from the server and mounted under a prefix with `nest`.
+///
+/// A router can carry middleware and a CORS policy that apply to its own routes and to the
+/// routes of routers nested in it, but to nothing else on the server:
+///
+/// ```ignore
+/// let users = Router::new()
+///   .route("/users", Rt::GET, handler!(list_users))
+///   .route("/users/{id}", Rt::GET, handler!(get_user))
+///   .middleware(RequireToken)
+///   .cors(CorsPolicy::from_config_str("origin=https://app.example"));
+/// server.nest("/api/v1", users);
+/// ```
+#[derive(Clone, Default)]
+pub struct Router {
+  routes: Vec<(Rt, String, Rh, Option<String>)>,
+  groups: Vec<(String, Router)>,
+  middleware: Vec<Arc<dyn Middleware>>,
+  cors: Option<CorsPolicy>,
+}
+
+impl Router {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Adds a route, with `path` relative to wherever the router gets mounted.
+  pub fn route(mut self, path: &str, rt: Rt, handler: Arc<dyn Handler>) -> Self {
+    self.routes.push((rt, path.to_string(), Rh { handler }, None));
+    self
+  }
+
+  /// Adds a route that `url_for` can build links to by `name`.
+  pub fn named_route(mut self, name: &str, path: &str, rt: Rt, handler: Arc<dyn Handler>) -> Self {
+    self.routes.push((rt, path.to_string(), Rh { handler }, Some(name.to_string())));
+    self
+  }
+
+  /// Mounts `router` under `prefix` inside this one. Its routes also run through this
+  /// router's middleware, outside their own.
+  pub fn nest(mut self, prefix: &str, router: Router) -> Self {
+    self.groups.push((prefix.to_string(), router));
+    self
+  }
+
+  /// Wraps every route of the group; middleware added first runs first.
+  pub fn middleware<M: Middleware + 'static>(mut self, middleware: M) -> Self {
+    self.middleware.push(Arc::new(middleware));
+    self
+  }
+
+  /// Applies `policy` to the group's responses instead of the server's policy and answers
+  /// preflight `OPTIONS` requests for its paths.
+  pub fn cors(mut self, policy: CorsPolicy) -> Self {
+    self.cors = Some(policy);
+    self
+  }
+}
+
+/// Answers a CORS group's preflight requests; the group's CORS middleware adds the headers.
+struct Preflight(CorsPolicy);
+
+#[async_trait]
+impl Handler for Preflight {
+  async fn handle(&self, _request: &Request) -> Response {
+    self.0.preflight_response()
+  }
+}
+
+/// Panics when a route cannot be added; see `RouteTree::try_from_routes`.
+impl FromIterator<((Rt, String), Rh)> for RouteTree {
+  fn from_iter<I: IntoIterator<Item = ((Rt, String), Rh)>>(routes: I) -> Self {
+    RouteTree::try_from_routes(routes).unwrap_or_else(|err| panic!("{}", err))
+  }
+}
@@ -0,0 +1,46 @@
+#![cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+
+/// Application state a server shares with every handler, at most one value per type.
+///
+/// Filled with `Server::with_state` and read with `Request::state`.
+#[derive(Clone, Default)]
+pub struct SharedState {
+  values: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+
+impl SharedState {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Stores `value`, replacing any earlier value of the same type.
+  pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) {
+    self.values.insert(TypeId::of::<T>(), Arc::new(value));
+  }
+
+  /// The stored value of type `T`, if any.
+  pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+    self
+      .values
+      .get(&TypeId::of::<T>())
+      .and_then(|value| value.downcast_ref::<T>())
+  }
+}
+
+impl Debug for SharedState {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    f.debug_struct("SharedState")
+      .field("values", &self.values.len())
+      .finish()
+  }
+}
@@ -1,128 +1,190 @@
 use std::fmt::{self, Display, Formatter};
+use std::hash::{Hash, Hasher};
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-#[repr(u16)]
-pub enum StatusCode {
-  Continue = 100,
-  SwitchingProtocols = 101,
-  Processing = 102,
-  Ok = 200,
-  Created = 201,
-  Accepted = 202,
-  NonAuthoritativeInformation = 203,
-  NoContent = 204,
-  ResetContent = 205,
-  PartialContent = 206,
-  MultipleChoices = 300,
-  MovedPermanently = 301,
-  Found = 302,
-  SeeOther = 303,
-  NotModified = 304,
-  UseProxy = 305,
-  TemporaryRedirect = 307,
-  PermanentRedirect = 308,
-  BadRequest = 400,
-  Unauthorized = 401,
-  PaymentRequired = 402,
-  Forbidden = 403,
-  NotFound = 404,
-  MethodNotAllowed = 405,
-  NotAcceptable = 406,
-  ProxyAuthenticationRequired = 407,
-  RequestTimeout = 408,
-  Conflict = 409,
-  Gone = 410,
-  LengthRequired = 411,
-  PreconditionFailed = 412,
-  PayloadTooLarge = 413,
-  UriTooLong = 414,
-  UnsupportedMediaType = 415,
-  RangeNotSatisfiable = 416,
-  ExpectationFailed = 417,
-  ImATeapot = 418,
-  MisdirectedRequest = 421,
-  UnprocessableEntity = 422,
-  Locked = 423,
-  FailedDependency = 424,
-  UpgradeRequired = 426,
-  PreconditionRequired = 428,
-  TooManyRequests = 429,
-  RequestHeaderFieldsTooLarge = 431,
-  UnavailableForLegalReasons = 451,
-  InternalServerError = 500,
-  NotImplemented = 501,
-  BadGateway = 502,
-  ServiceUnavailable = 503,
-  GatewayTimeout = 504,
-  HttpVersionNotSupported = 505,
-  VariantAlsoNegotiates = 506,
-  InsufficientStorage = 507,
-  LoopDetected = 508,
-  NotExtended = 510,
-  NetworkAuthenticationRequired = 511,
+macro_rules! status_codes {
+  ($($name:ident = $code:literal, $reason:literal;)+) => {
+    /// An HTTP status code.
+    ///
+    /// Registered codes have their own variant; anything else in 100..=999 is a `Custom`
+    /// code with the reason phrase to send. Codes compare by number only, so
+    /// `StatusCode::Custom(404, "Gone Fishing") == StatusCode::NotFound`.
+    #[derive(Copy, Clone, Debug)]
+    pub enum StatusCode {
+      $($name,)+
+      Custom(u16, &'static str),
+    }
+
+    impl StatusCode {
+      pub const fn as_u16(&self) -> u16 {
+        match *self {
+          $(StatusCode::$name => $code,)+
+          StatusCode::Custom(code, _) => code,
+        }
+      }
+
+      /// The reason phrase sent after the code in the status line.
+      pub const fn reason_phrase(&self) -> &'static str {
+        match *self {
+          $(StatusCode::$name => $reason,)+
+          StatusCode::Custom(_, reason) => reason,
+        }
+      }
+
+      /// Maps a number to its registered variant, or to `Custom` with an empty reason phrase.
+      /// Fails outside 100..=999, the range a status line can carry.
+      pub const fn from_u16(code: u16) -> Result<Self, InvalidStatusCode> {
+        match code {
+          $($code => Ok(StatusCode::$name),)+
+          100..=999 => Ok(StatusCode::Custom(code, "")),
+          _ => Err(InvalidStatusCode(code)),
+        }
+      }
+    }
+  };
+}
+
+status_codes! {
+  Continue = 100, "Continue";
+  SwitchingProtocols = 101, "Switching Protocols";
+  Processing = 102, "Processing";
+  Ok = 200, "OK";
+  Created = 201, "Created";
+  Accepted = 202, "Accepted";
+  NonAuthoritativeInformation = 203, "Non-Authoritative Information";
+  NoContent = 204, "No Content";
+  ResetContent = 205, "Reset Content";
+  PartialContent = 206, "Partial Content";
+  MultipleChoices = 300, "Multiple Choices";
+  MovedPermanently = 301, "Moved Permanently";
+  Found = 302, "Found";
+  SeeOther = 303, "See Other";
+  NotModified = 304, "Not Modified";
+  UseProxy = 305, "Use Proxy";
+  TemporaryRedirect = 307, "Temporary Redirect";
+  PermanentRedirect = 308, "Permanent Redirect";
+  BadRequest = 400, "Bad Request";
+  Unauthorized = 401, "Unauthorized";
+  PaymentRequired = 402, "Payment Required";
+  Forbidden = 403, "Forbidden";
+  NotFound = 404, "Not Found";
+  MethodNotAllowed = 405, "Method Not Allowed";
+  NotAcceptable = 406, "Not Acceptable";
+  ProxyAuthenticationRequired = 407, "Proxy Authentication Required";
+  RequestTimeout = 408, "Request Timeout";
+  Conflict = 409, "Conflict";
+  Gone = 410, "Gone";
+  LengthRequired = 411, "Length Required";
+  PreconditionFailed = 412, "Precondition Failed";
+  PayloadTooLarge = 413, "Payload Too Large";
+  UriTooLong = 414, "URI Too Long";
+  UnsupportedMediaType = 415, "Unsupported Media Type";
+  RangeNotSatisfiable = 416, "Range Not Satisfiable";
+  ExpectationFailed = 417, "Expectation Failed";
+  ImATeapot = 418, "I'm a Teapot";
+  MisdirectedRequest = 421, "Misdirected Request";
+  UnprocessableEntity = 422, "Unprocessable Entity";
+  Locked = 423, "Locked";
+  FailedDependency = 424, "Failed Dependency";
+  UpgradeRequired = 426, "Upgrade Required";
+  PreconditionRequired = 428, "Precondition Required";
+  TooManyRequests = 429, "Too Many Requests";
+  RequestHeaderFieldsTooLarge = 431, "Request Header Fields Too Large";
+  UnavailableForLegalReasons = 451, "Unavailable For Legal Reasons";
+  InternalServerError = 500, "Internal Server Error";
+  NotImplemented = 501, "Not Implemented";
+  BadGateway = 502, "Bad Gateway";
+  ServiceUnavailable = 503, "Service Unavailable";
+  GatewayTimeout = 504, "Gateway Timeout";
+  HttpVersionNotSupported = 505, "HTTP Version Not Supported";
+  VariantAlsoNegotiates = 506, "Variant Also Negotiates";
+  InsufficientStorage = 507, "Insufficient Storage";
+  LoopDetected = 508, "Loop Detected";
+  NotExtended = 510, "Not Extended";
+  NetworkAuthenticationRequired = 511, "Network Authentication Required";
+}
+
+impl StatusCode {
+  /// 1xx
+  pub const fn is_informational(&self) -> bool {
+    matches!(self.as_u16(), 100..=199)
+  }
+
+  /// 2xx
+  pub const fn is_success(&self) -> bool {
+    matches!(self.as_u16(), 200..=299)
+  }
+
+  /// 3xx
+  pub const fn is_redirection(&self) -> bool {
+    matches!(self.as_u16(), 300..=399)
+  }
+
+  /// 4xx
+  pub const fn is_client_error(&self) -> bool {
+    matches!(self.as_u16(), 400..=499)
+  }
+
+  /// 5xx
+  pub const fn is_server_error(&self) -> bool {
+    matches!(self.as_u16(), 500..=599)
+  }
+
+  /// Whether a status line can carry it: the code is in 100..=999 and the reason phrase has
+  /// no control characters besides tab. Only a `Custom` code can fail this.
+  pub fn is_valid(&self) -> bool {
+    let reason_ok = !self.reason_phrase().chars().any(|c| c.is_ascii_control() && c != '\t');
+    matches!(self.as_u16(), 100..=999) && reason_ok
+  }
+}
+
+impl PartialEq for StatusCode {
+  fn eq(&self, other: &Self) -> bool {
+    self.as_u16() == other.as_u16()
+  }
+}
+
+impl Eq for StatusCode {}
+
+impl Hash for StatusCode {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.as_u16().hash(state);
+  }
+}
+
+impl PartialEq<u16> for StatusCode {
+  fn eq(&self, other: &u16) -> bool {
+    self.as_u16() == *other
+  }
+}
+
+impl From<StatusCode> for u16 {
+  fn from(status: StatusCode) -> u16 {
+    status.as_u16()
+  }
+}
+
+impl TryFrom<u16> for StatusCode {
+  type Error = InvalidStatusCode;
+
+  fn try_from(code: u16) -> Result<Self, Self::Error> {
+    StatusCode::from_u16(code)
+  }
 }
 
 impl Display for StatusCode {
   fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-    let text = match self {
-      StatusCode::Continue => "100 Continue",
-      StatusCode::SwitchingProtocols => "101 Switching Protocols",
-      StatusCode::Processing => "102 Processing",
-      StatusCode::Ok => "200 OK",
-      StatusCode::Created => "201 Created",
-      StatusCode::Accepted => "202 Accepted",
-      StatusCode::NonAuthoritativeInformation => "203 Non‑Authoritative Information",
-      StatusCode::NoContent => "204 No Content",
-      StatusCode::ResetContent => "205 Reset Content",
-      StatusCode::PartialContent => "206 Partial Content",
-      StatusCode::MultipleChoices => "300 Multiple Choices",
-      StatusCode::MovedPermanently => "301 Moved Permanently",
-      StatusCode::Found => "302 Found",
-      StatusCode::SeeOther => "303 See Other",
-      StatusCode::NotModified => "304 Not Modified",
-      StatusCode::UseProxy => "305 Use Proxy",
-      StatusCode::TemporaryRedirect => "307 Temporary Redirect",
-      StatusCode::PermanentRedirect => "308 Permanent Redirect",
-      StatusCode::BadRequest => "400 Bad Request",
-      StatusCode::Unauthorized => "401 Unauthorized",
-      StatusCode::PaymentRequired => "402 Payment Required",
-      StatusCode::Forbidden => "403 Forbidden",
-      StatusCode::NotFound => "404 Not Found",
-      StatusCode::MethodNotAllowed => "405 Method Not Allowed",
-      StatusCode::NotAcceptable => "406 Not Acceptable",
-      StatusCode::ProxyAuthenticationRequired => "407 Proxy Authentication Required",
-      StatusCode::RequestTimeout => "408 Request Timeout",
-      StatusCode::Conflict => "409 Conflict",
-      StatusCode::Gone => "410 Gone",
-      StatusCode::LengthRequired => "411 Length Required",
-      StatusCode::PreconditionFailed => "412 Precondition Failed",
-      StatusCode::PayloadTooLarge => "413 Payload Too Large",
-      StatusCode::UriTooLong => "414 URI Too Long",
-      StatusCode::UnsupportedMediaType => "415 Unsupported Media Type",
-      StatusCode::RangeNotSatisfiable => "416 Range Not Satisfiable",
-      StatusCode::ExpectationFailed => "417 Expectation Failed",
-      StatusCode::ImATeapot => "418 I'm a Teapot",
-      StatusCode::MisdirectedRequest => "421 Misdirected Request",
-      StatusCode::UnprocessableEntity => "422 Unprocessable Entity",
-      StatusCode::Locked => "423 Locked",
-      StatusCode::FailedDependency => "424 Failed Dependency",
-      StatusCode::UpgradeRequired => "426 Upgrade Required",
-      StatusCode::PreconditionRequired => "428 Precondition Required",
-      StatusCode::TooManyRequests => "429 Too Many Requests",
-      StatusCode::RequestHeaderFieldsTooLarge => "431 Request Header Fields Too Large",
-      StatusCode::UnavailableForLegalReasons => "451 Unavailable For Legal Reasons",
-      StatusCode::InternalServerError => "500 Internal Server Error",
-      StatusCode::NotImplemented => "501 Not Implemented",
-      StatusCode::BadGateway => "502 Bad Gateway",
-      StatusCode::ServiceUnavailable => "503 Service Unavailable",
-      StatusCode::GatewayTimeout => "504 Gateway Timeout",
-      StatusCode::HttpVersionNotSupported => "505 HTTP Version Not Supported",
-      StatusCode::VariantAlsoNegotiates => "506 Variant Also Negotiates",
-      StatusCode::InsufficientStorage => "507 Insufficient Storage",
-      StatusCode::LoopDetected => "508 Loop Detected",
-      StatusCode::NotExtended => "510 Not Extended",
-      StatusCode::NetworkAuthenticationRequired => "511 Network Authentication Required",
-    };
-    write!(f, "{}", text)
+    write!(f, "{} {}", self.as_u16(), self.reason_phrase())
+  }
+}
+
+/// Returned by `StatusCode::from_u16` for numbers outside 100..=999.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct InvalidStatusCode(pub u16);
+
+impl Display for InvalidStatusCode {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    write!(f, "invalid HTTP status code {}", self.0)
   }
 }
+
+impl std::error::Error for InvalidStatusCode {}
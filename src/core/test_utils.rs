@@ -55,7 +55,7 @@ use crate::runtime::r#async::smol::Server;
 use crate::runtime::r#async::async_std::Server;
 
 thread_local! {
-  static ACTIVE_SERVER_URL: RefCell<Option<&'static str>> = RefCell::new(None);
+  static ACTIVE_SERVER_URL: RefCell<Option<&'static str>> = const { RefCell::new(None) };
 }
 
 fn server_registry() -> &'static Mutex<HashMap<String, &'static str>> {
@@ -78,7 +78,7 @@ fn set_active_url(url: &'static str) {
 }
 
 pub fn active_test_server_url() -> &'static str {
-  if let Some(url) = ACTIVE_SERVER_URL.with(|slot| slot.borrow().clone()) {
+  if let Some(url) = ACTIVE_SERVER_URL.with(|slot| *slot.borrow()) {
     return url;
   }
 
@@ -86,7 +86,8 @@ pub fn active_test_server_url() -> &'static str {
     .get_or_init(|| Mutex::new(None))
     .lock()
     .unwrap_or_else(|err| err.into_inner())
-    .clone()
+    .as_ref()
+    .copied()
   {
     set_active_url(url);
     return url;
@@ -102,6 +103,15 @@ pub fn active_test_server_url() -> &'static str {
   DEFAULT_TEST_SERVER_URL
 }
 
+/// The address the server set up for `server_url` listens on, which differs from
+/// `server_url` when its factory had to bind another port. `server_url` itself when no
+/// server was set up for it.
+pub fn test_server_url(server_url: &str) -> String {
+  registry_guard()
+    .get(server_url)
+    .map_or_else(|| server_url.to_string(), |url| url.to_string())
+}
+
 #[cfg(feature = "sync")]
 fn wait_for_server(url: &str) {
   for _ in 0..WAIT_ATTEMPTS {
@@ -148,7 +158,7 @@ where
   let server_url = server_url.unwrap_or_else(|| active_test_server_url());
   let mut registry = registry_guard();
   if let Some(url) = registry.get(server_url) {
-    set_active_url(*url);
+    set_active_url(url);
     return;
   }
 
@@ -168,7 +178,7 @@ where
 #[cfg(feature = "sync")]
 pub fn run_test(request: &[u8], expected_response: &[u8], target_url: Option<&str>) -> String {
   let url = target_url
-    .map(|s| s.to_string())
+    .map(test_server_url)
     .unwrap_or_else(|| active_test_server_url().to_string());
   perform_test(&url, request, expected_response)
 }
@@ -183,7 +193,7 @@ where
   let server_url = server_url.unwrap_or_else(|| active_test_server_url());
   let mut registry = registry_guard();
   if let Some(url) = registry.get(server_url) {
-    set_active_url(*url);
+    set_active_url(url);
     return;
   }
 
@@ -212,7 +222,7 @@ where
 pub async fn run_test(request: &[u8], expected_response: &[u8], target_url: Option<&str>) -> String {
   use tokio::io::{AsyncReadExt, AsyncWriteExt};
   let url = target_url
-    .map(|s| s.to_string())
+    .map(test_server_url)
     .unwrap_or_else(|| active_test_server_url().to_string());
   let mut stream = {
     let mut attempt = 0;
@@ -263,7 +273,7 @@ where
   let server_url = server_url.unwrap_or_else(|| active_test_server_url());
   let mut registry = registry_guard();
   if let Some(url) = registry.get(server_url) {
-    set_active_url(*url);
+    set_active_url(url);
     return;
   }
 
@@ -289,7 +299,7 @@ pub async fn run_test(request: &[u8], expected_response: &[u8], target_url: Opti
   use async_std::io::prelude::*;
   use async_std::net::{Shutdown, TcpStream};
   let url = target_url
-    .map(|s| s.to_string())
+    .map(test_server_url)
     .unwrap_or_else(|| active_test_server_url().to_string());
   let mut stream = {
     let mut attempt = 0;
@@ -342,7 +352,7 @@ where
   let server_url = server_url.unwrap_or_else(|| active_test_server_url());
   let mut registry = registry_guard();
   if let Some(url) = registry.get(server_url) {
-    set_active_url(*url);
+    set_active_url(url);
     return;
   }
 
@@ -371,7 +381,7 @@ pub async fn run_test(request: &[u8], expected_response: &[u8], target_url: Opti
   use smol::io::AsyncReadExt;
   use smol::io::AsyncWriteExt;
   let url = target_url
-    .map(|s| s.to_string())
+    .map(test_server_url)
     .unwrap_or_else(|| active_test_server_url().to_string());
   let mut stream = {
     let mut attempt = 0;
@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::path::{Path, PathBuf};
 
 pub fn get_content_type_quick(path: &Path) -> String {
@@ -56,3 +57,46 @@ pub fn secure_path(base: &Path, req_path: &str) -> Option<PathBuf> {
     None
   }
 }
+
+/// Decodes the `%XX` escapes of a path segment. Malformed escapes are kept as they are and
+/// bytes that do not form UTF-8 are replaced with U+FFFD.
+pub fn percent_decode(text: &str) -> Cow<'_, str> {
+  decode(text, false)
+}
+
+/// Decodes a query string key or value as `application/x-www-form-urlencoded`: like
+/// `percent_decode`, with `+` standing for a space.
+pub fn form_decode(text: &str) -> Cow<'_, str> {
+  decode(text, true)
+}
+
+fn decode(text: &str, plus_as_space: bool) -> Cow<'_, str> {
+  let escaped = |byte: u8| byte == b'%' || (plus_as_space && byte == b'+');
+  if !text.bytes().any(escaped) {
+    return Cow::Borrowed(text);
+  }
+  let bytes = text.as_bytes();
+  let mut decoded = Vec::with_capacity(bytes.len());
+  let mut i = 0;
+  while i < bytes.len() {
+    let escaped = bytes
+      .get(i + 1..i + 3)
+      .filter(|_| bytes[i] == b'%')
+      .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+    match escaped {
+      Some(byte) => {
+        decoded.push(byte);
+        i += 3;
+      }
+      None if plus_as_space && bytes[i] == b'+' => {
+        decoded.push(b' ');
+        i += 1;
+      }
+      None => {
+        decoded.push(bytes[i]);
+        i += 1;
+      }
+    }
+  }
+  Cow::Owned(String::from_utf8_lossy(&decoded).into_owned())
+}
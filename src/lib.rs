@@ -3,9 +3,11 @@ pub mod core;
 // Common re-exports (always available)
 pub use crate::core::{
   cors::CorsPolicy,
+  extensions::Extensions,
+  limits::ServerLimits,
   request_type::Rt,
-  response::Response,
-  status_code::StatusCode,
+  response::{BodyStream, IntoResponse, Response, ResponseBuilder},
+  status_code::{InvalidStatusCode, StatusCode},
   test_utils,
 };
 
@@ -16,7 +18,16 @@ pub use crate::core::{
   feature = "async_std",
   feature = "async_smol"
 ))]
-pub use crate::core::{handler::Handler, request::Request, request_handler::Rh};
+pub use crate::core::{
+  error_pages::{ErrorPages, ErrorRenderer, problem_json},
+  extract::{FromPath, FromRequest, Path, Rejection, State},
+  handler::Handler,
+  middleware::{Middleware, Next},
+  request::{ParamError, Request},
+  request_handler::Rh,
+  router::{RouteError, RouteInfo, Router, UrlError},
+  state::SharedState,
+};
 
 pub mod runtime {
   #[cfg(feature = "sync")]
@@ -39,6 +50,17 @@ pub mod runtime {
   pub mod shared;
 }
 
+#[cfg(all(
+  any(
+    feature = "sync",
+    feature = "async_tokio",
+    feature = "async_std",
+    feature = "async_smol"
+  ),
+  feature = "json"
+))]
+pub use crate::core::extract::{Json, Query};
+
 // Server export selection
 #[cfg(feature = "sync")]
 pub use runtime::sync::server::Server;
@@ -73,6 +95,7 @@ pub struct Server;
   not(feature = "async_std")
 ))]
 impl Server {
+  #[allow(clippy::new_without_default)]
   pub fn new() -> Self {
     eprintln!(
       "\n❌ No feature is active.\n\nActivate a feature when compiling:\n\n    cargo run --features sync\n    cargo run --features async_tokio\n    cargo run --features async_std\n    cargo run --features async_smol\n"
@@ -17,7 +17,7 @@ use httpageboy::{Request, Response, Rt, Server, StatusCode, handler};
 #[cfg(feature = "sync")]
 fn demo_get(_request: &Request) -> Response {
   Response {
-    status: StatusCode::Ok.to_string(),
+    status: StatusCode::Ok,
     content_type: String::new(),
     content: "<!DOCTYPE html><html><head>\
 <meta charset=\"utf-8\">\
@@ -25,6 +25,7 @@ fn demo_get(_request: &Request) -> Response {
 <br>Do you like the <a href=\"/HTTPageboy.svg\">new icon</a>?</body></html>"
       .as_bytes()
       .to_vec(),
+    ..Default::default()
   }
 }
 
@@ -38,7 +39,7 @@ async fn demo_get(_request: &Request) -> Response {
   SmolTimer::after(Duration::from_millis(100)).await;
 
   Response {
-    status: StatusCode::Ok.to_string(),
+    status: StatusCode::Ok,
     content_type: String::new(),
     content: "<!DOCTYPE html><html><head>\
 <meta charset=\"utf-8\">\
@@ -46,6 +47,7 @@ async fn demo_get(_request: &Request) -> Response {
 <br>Do you like the <a href=\"/HTTPageboy.svg\">new icon</a>?</body></html>"
       .as_bytes()
       .to_vec(),
+    ..Default::default()
   }
 }
 
@@ -1,12 +1,22 @@
-use crate::core::handler::Handler;
+use crate::core::chunked::{LAST_CHUNK, encode_chunk};
 use crate::core::cors::CorsPolicy;
+use crate::core::error_pages::ErrorPages;
+use crate::core::handler::Handler;
+use crate::core::limits::ServerLimits;
+use crate::core::middleware::{Dispatcher, Middleware};
+use crate::core::request::Request;
 use crate::core::request_handler::Rh;
 use crate::core::request_type::Rt;
 use crate::core::response::Response;
+use crate::core::router::{RouteError, RouteInfo, RouteTree, Router, UrlError};
+use crate::core::state::SharedState;
+use crate::core::status_code::StatusCode;
 use async_trait::async_trait;
-use std::collections::HashMap;
+use futures::future::{Either, select};
+use std::future::Future;
 use std::io::Result;
 use std::sync::Arc;
+use std::time::Duration;
 
 /// A trait that abstracts over the different async TCP streams.
 /// This allows us to write generic code that can work with any of the supported runtimes.
@@ -17,34 +27,60 @@ pub trait AsyncStream: Send + Sync {
   async fn shutdown(&mut self) -> Result<()>;
 }
 
+/// Awaits `fut` for at most `dur` using the active runtime's timer.
+/// Returns `None` when the deadline elapses first.
+pub async fn timeout<F: Future>(dur: Duration, fut: F) -> Option<F::Output> {
+  #[cfg(feature = "async_tokio")]
+  let sleep = tokio::time::sleep(dur);
+  #[cfg(all(feature = "async_std", not(feature = "async_tokio")))]
+  let sleep = async_std::task::sleep(dur);
+  #[cfg(all(feature = "async_smol", not(any(feature = "async_tokio", feature = "async_std"))))]
+  let sleep = smol::Timer::after(dur);
+  futures::pin_mut!(fut, sleep);
+  match select(fut, sleep).await {
+    Either::Left((out, _)) => Some(out),
+    Either::Right(_) => None,
+  }
+}
+
 /// Sends a response to the client over the given stream.
-pub async fn send_response<S: AsyncStream>(
-  stream: &mut S,
-  resp: &Response,
-  close: bool,
-  cors: Option<&CorsPolicy>,
-  origin: Option<&str>,
-) {
+/// Streaming bodies are written with `Transfer-Encoding: chunked`, everything else with `Content-Length`.
+/// For a HEAD request only the header section is written, still describing the body a GET would get.
+pub async fn send_response<S: AsyncStream>(stream: &mut S, mut resp: Response, close: bool, head_request: bool) {
+  let body_stream = resp.stream.take();
   let conn_hdr = if close { "Connection: close\r\n" } else { "" };
+  let length_hdr = match body_stream {
+    Some(_) => "Transfer-Encoding: chunked\r\n".to_string(),
+    None => format!("Content-Length: {}\r\n", resp.content.len()),
+  };
   let mut head = format!(
-    "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}",
-    resp.status,
-    resp.content_type,
-    resp.content.len(),
+    "{}{}{}{}",
+    resp.status_line(),
+    resp.content_type_line(),
+    length_hdr,
     conn_hdr,
   );
-  if let Some(policy) = cors {
-    for (k, v) in policy.header_lines(origin) {
-      head.push_str(&format!("{}: {}\r\n", k, v));
-    }
-  }
+  head.push_str(&resp.header_lines());
   head.push_str("\r\n");
   let _ = stream.write_all(head.as_bytes()).await;
-  if resp.content_type.starts_with("image/") {
-    let _ = stream.write_all(&resp.content).await;
-  } else {
-    let text = String::from_utf8_lossy(&resp.content);
-    let _ = stream.write_all(text.as_bytes()).await;
+  match body_stream {
+    _ if head_request => {}
+    Some(mut body) => {
+      let mut piece = std::mem::take(&mut resp.content);
+      loop {
+        if !piece.is_empty() && stream.write_all(&encode_chunk(&piece)).await.is_err() {
+          break;
+        }
+        match body.next_chunk().await {
+          Some(next) => piece = next,
+          None => break,
+        }
+      }
+      let _ = stream.write_all(LAST_CHUNK).await;
+    }
+    None => {
+      let _ = stream.write_all(&resp.content).await;
+    }
   }
   let _ = stream.flush().await;
   if close {
@@ -57,23 +93,191 @@ pub async fn send_response<S: AsyncStream>(
 pub struct GenericServer<L> {
   pub listener: L,
   pub url: String,
-  pub routes: Arc<HashMap<(Rt, String), Rh>>,
+  pub routes: Arc<RouteTree>,
   pub files_sources: Arc<Vec<String>>,
   pub auto_close: bool,
+  pub idle_timeout: Duration,
+  pub max_requests: usize,
+  pub limits: ServerLimits,
   pub cors: Option<Arc<CorsPolicy>>,
+  pub error_pages: Arc<ErrorPages>,
+  pub middleware: Vec<Arc<dyn Middleware>>,
+  pub state: Arc<SharedState>,
 }
 
 impl<L> GenericServer<L> {
-  /// Toggles the `Connection: close` header.
+  /// When `true` (the default) every connection is closed after one response;
+  /// `false` keeps HTTP/1.1 connections open for further requests.
   pub fn set_auto_close(&mut self, active: bool) {
     self.auto_close = active;
   }
 
+  /// How long a kept-alive connection may sit without a new request before it is closed.
+  /// A zero timeout closes every connection after its first response.
+  ///
+  /// Each open connection keeps one of the `pool_size` worker threads busy, idle or not, so
+  /// while other connections wait for a worker, a kept-alive connection is closed after its
+  /// current response instead of waiting for another request.
+  pub fn set_idle_timeout(&mut self, timeout: Duration) {
+    self.idle_timeout = timeout;
+  }
+
+  /// Number of requests served on one connection before it is closed (minimum 1).
+  pub fn set_max_requests_per_connection(&mut self, max: usize) {
+    self.max_requests = max.max(1);
+  }
+
+  /// Replaces the timeouts and size caps applied while reading requests.
+  pub fn set_limits(&mut self, limits: ServerLimits) {
+    self.limits = limits;
+  }
+
+  /// Handles requests that match no route and no static file, which otherwise get a 404.
+  pub fn set_fallback(&mut self, handler: Arc<dyn Handler>) {
+    Arc::get_mut(&mut self.error_pages).unwrap().set_fallback(handler);
+  }
+
+  /// Renders the error responses with `status` that the server builds itself: unmatched
+  /// requests without a fallback, 405 answers and requests the parser refuses.
+  pub fn set_error_renderer<F>(&mut self, status: StatusCode, renderer: F)
+  where
+    F: Fn(&Request, StatusCode) -> Response + Send + Sync + 'static,
+  {
+    Arc::get_mut(&mut self.error_pages)
+      .unwrap()
+      .set_renderer(status, Arc::new(renderer));
+  }
+
+  /// Like `set_error_renderer`, for every 4xx and 5xx status without a renderer of its own,
+  /// e.g. `problem_json`.
+  pub fn set_default_error_renderer<F>(&mut self, renderer: F)
+  where
+    F: Fn(&Request, StatusCode) -> Response + Send + Sync + 'static,
+  {
+    Arc::get_mut(&mut self.error_pages)
+      .unwrap()
+      .set_default_renderer(Arc::new(renderer));
+  }
+
+  /// Runs `middleware` around every request the server parses, routed or not. Global
+  /// middleware runs in the order it was added, inside the CORS policy and outside any route's
+  /// own middleware.
+  pub fn add_middleware<M: Middleware + 'static>(&mut self, middleware: M) {
+    self.middleware.push(Arc::new(middleware));
+  }
+
+  /// Shares `state` with every handler, which reads it with `request.state::<T>()`. Each
+  /// type holds one value; giving another value of the same type replaces it.
+  pub fn with_state<T: Send + Sync + 'static>(&mut self, state: T) {
+    Arc::get_mut(&mut self.state).unwrap().insert(state);
+  }
+
+  /// Bundles what the connection tasks need to answer parsed requests.
+  pub(crate) fn dispatcher(&self) -> Dispatcher {
+    Dispatcher::new(
+      &self.routes,
+      &self.files_sources,
+      &self.error_pages,
+      self.cors.as_ref(),
+      &self.middleware,
+      &self.state,
+    )
+  }
+
   /// Adds a new route to the server.
+  ///
+  /// Panics when `try_add_route` would fail.
   pub fn add_route(&mut self, path: &str, rt: Rt, handler: Arc<dyn Handler>) {
+    self
+      .try_add_route(path, rt, handler)
+      .unwrap_or_else(|err| panic!("{}", err));
+  }
+
+  /// Adds a route, failing when `path` is malformed or another route for `rt` already matches
+  /// the same paths.
+  pub fn try_add_route(
+    &mut self,
+    path: &str,
+    rt: Rt,
+    handler: Arc<dyn Handler>,
+  ) -> std::result::Result<(), RouteError> {
     Arc::get_mut(&mut self.routes)
       .unwrap()
-      .insert((rt, path.to_string()), Rh { handler });
+      .try_insert(rt, path, Rh { handler })
+  }
+
+  /// Adds a route whose handler runs through `middleware`, outermost first.
+  ///
+  /// Panics like `add_route`.
+  pub fn add_route_with(
+    &mut self,
+    path: &str,
+    rt: Rt,
+    handler: Arc<dyn Handler>,
+    middleware: Vec<Arc<dyn Middleware>>,
+  ) {
+    self
+      .try_add_route_with(path, rt, handler, middleware)
+      .unwrap_or_else(|err| panic!("{}", err));
+  }
+
+  /// Like `add_route_with`, returning the `RouteError` instead of panicking.
+  pub fn try_add_route_with(
+    &mut self,
+    path: &str,
+    rt: Rt,
+    handler: Arc<dyn Handler>,
+    middleware: Vec<Arc<dyn Middleware>>,
+  ) -> std::result::Result<(), RouteError> {
+    Arc::get_mut(&mut self.routes)
+      .unwrap()
+      .try_insert_with(rt, path, Rh { handler }, middleware)
+  }
+
+  /// Adds a route that `url_for` can build links to by `name`.
+  ///
+  /// Panics like `add_route`, or when `name` is already taken.
+  pub fn add_named_route(&mut self, name: &str, path: &str, rt: Rt, handler: Arc<dyn Handler>) {
+    self
+      .try_add_named_route(name, path, rt, handler)
+      .unwrap_or_else(|err| panic!("{}", err));
+  }
+
+  /// Like `add_named_route`, returning the `RouteError` instead of panicking. Nothing is added
+  /// when the route or its name is refused.
+  pub fn try_add_named_route(
+    &mut self,
+    name: &str,
+    path: &str,
+    rt: Rt,
+    handler: Arc<dyn Handler>,
+  ) -> std::result::Result<(), RouteError> {
+    Arc::get_mut(&mut self.routes)
+      .unwrap()
+      .try_insert_named(name, rt, path, Rh { handler }, Vec::new())
+  }
+
+  /// Builds the path of the route registered as `name`, see `RouteTree::url_for`.
+  pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> std::result::Result<String, UrlError> {
+    self.routes.url_for(name, params)
+  }
+
+  /// Adds every route of `router` under `prefix`, e.g. `/api/v1`.
+  ///
+  /// Panics when one of them cannot be added, see `add_named_route`.
+  pub fn nest(&mut self, prefix: &str, router: Router) {
+    self.try_nest(prefix, router).unwrap_or_else(|err| panic!("{}", err));
+  }
+
+  /// Like `nest`, returning the `RouteError` instead of panicking. On failure none of the
+  /// routes of `router` are added.
+  pub fn try_nest(&mut self, prefix: &str, router: Router) -> std::result::Result<(), RouteError> {
+    Arc::get_mut(&mut self.routes).unwrap().try_mount(prefix, router)
+  }
+
+  /// Every registered route with its method, pattern and name, in the order they were added.
+  pub fn routes(&self) -> &[RouteInfo] {
+    self.routes.routes()
   }
 
   pub fn url(&self) -> &str {
@@ -1,9 +1,13 @@
 use crate::core::cors::CorsPolicy;
-use crate::core::request::handle_request_async;
+use crate::core::error_pages::ErrorPages;
+use crate::core::limits::ServerLimits;
+use crate::core::request::{Request, error_response};
 use crate::core::request_handler::Rh;
-use crate::core::response::Response;
+use crate::core::router::RouteTree;
+use crate::core::state::SharedState;
 use crate::runtime::r#async::shared;
-use crate::runtime::shared::print_server_info;
+use crate::core::status_code::StatusCode;
+use crate::runtime::shared::{DEFAULT_IDLE_TIMEOUT, DEFAULT_MAX_REQUESTS_PER_CONNECTION, print_server_info};
 use async_trait::async_trait;
 use smol::io::AsyncWriteExt;
 use smol::net::{TcpListener, TcpStream};
@@ -45,19 +49,27 @@ impl DerefMut for Server {
 
 impl Server {
   /// Creates a new server and binds to the specified URL.
+  /// Fails with `InvalidInput` when a route of `routes_list` cannot be added (see `RouteError`).
   pub async fn new(
     serving_url: &str,
     routes_list: Option<HashMap<(crate::core::request_type::Rt, String), Rh>>,
   ) -> std::io::Result<Self> {
+    let routes = RouteTree::try_from_routes(routes_list.unwrap_or_default())?;
     let listener = TcpListener::bind(serving_url).await?;
     let url = listener.local_addr()?.to_string();
     Ok(Server(shared::GenericServer {
       listener,
       url,
-      routes: Arc::new(routes_list.unwrap_or_default()),
+      routes: Arc::new(routes),
       files_sources: Arc::new(Vec::new()),
       auto_close: true,
+      idle_timeout: DEFAULT_IDLE_TIMEOUT,
+      max_requests: DEFAULT_MAX_REQUESTS_PER_CONNECTION,
+      limits: ServerLimits::default(),
       cors: Some(Arc::new(CorsPolicy::default())),
+      error_pages: Arc::new(ErrorPages::new()),
+      middleware: Vec::new(),
+      state: Arc::new(SharedState::new()),
     }))
   }
 
@@ -70,6 +82,7 @@ impl Server {
     self.0.url.as_str()
   }
 
+  /// Replaces the CORS policy, which runs as the outermost global middleware.
   pub fn set_cors(&mut self, policy: CorsPolicy) {
     self.0.cors = Some(Arc::new(policy));
   }
@@ -81,47 +94,52 @@ impl Server {
   /// Starts the server and begins accepting connections.
   pub async fn run(&self) {
     print_server_info(self.listener.local_addr().unwrap(), self.auto_close);
+    let dispatcher = Arc::new(self.dispatcher());
     loop {
-      if let Ok((mut stream, _)) = self.listener.accept().await {
-        let routes = self.routes.clone();
-        let files = self.files_sources.clone();
-        let close_flag = self.auto_close;
-        let cors_policy = self.cors.clone();
+      if let Ok((stream, _)) = self.listener.accept().await {
+        let dispatcher = dispatcher.clone();
+        let close_flag = self.auto_close || self.idle_timeout.is_zero();
+        let idle_timeout = self.idle_timeout;
+        let max_requests = self.max_requests;
+        let limits = self.limits;
 
         spawn(async move {
-          let (mut req, early) = crate::core::request::parse_stream_smol(
-            &mut stream,
-            &routes,
-            &files,
-          )
-          .await;
-          let origin = req.origin().map(str::to_string);
-          let method = req.method.clone();
-          let resp = match early {
-            Some(r) => r,
-            None => {
-              let routed = handle_request_async(&mut req, &routes, &files).await;
-              if routed.is_none()
-                && method == crate::core::request_type::RequestType::OPTIONS
-                && cors_policy.is_some()
-              {
-                cors_policy
-                  .as_deref()
-                  .map(|policy| policy.preflight_response())
-                  .unwrap_or_else(Response::new)
-              } else {
-                routed.unwrap_or_else(Response::new)
+          let mut reader = futures_lite::io::BufReader::new(stream);
+          let first_line_timeout = if close_flag {
+            limits.header_read_timeout
+          } else {
+            idle_timeout
+          };
+          let mut served = 0;
+          loop {
+            let Some((mut req, early)) =
+              crate::core::request::parse_stream_smol(&mut reader, &limits, first_line_timeout, close_flag).await
+            else {
+              if served == 0 {
+                let resp = error_response(StatusCode::BadRequest);
+                let resp = dispatcher.refuse(&Request::default(), resp);
+                shared::send_response(reader.get_mut(), resp, true, false).await;
               }
+              break;
+            };
+            served += 1;
+            let method = req.method.clone();
+            let resp = match early {
+              Some(r) => dispatcher.refuse(&req, r),
+              None => dispatcher.handle(&mut req).await,
+            };
+            let close = close_flag || !req.keep_alive() || served >= max_requests;
+            shared::send_response(
+              reader.get_mut(),
+              resp,
+              close,
+              method == crate::core::request_type::RequestType::HEAD,
+            )
+            .await;
+            if close {
+              break;
             }
-          };
-          shared::send_response(
-            &mut stream,
-            &resp,
-            close_flag,
-            cors_policy.as_deref(),
-            origin.as_deref(),
-          )
-          .await;
+          }
         })
         .detach();
       }
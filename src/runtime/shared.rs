@@ -1,3 +1,11 @@
+use std::time::Duration;
+
+/// Default time a kept-alive connection may stay idle between requests.
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default number of requests served on one kept-alive connection.
+pub const DEFAULT_MAX_REQUESTS_PER_CONNECTION: usize = 100;
+
 pub fn print_server_info(addr: std::net::SocketAddr, _auto_close: bool) {
   // println!("Connection autoclose set to {:?}", _auto_close);
 
@@ -1,40 +1,80 @@
 #![cfg(feature = "sync")]
 
+use crate::core::chunked::{LAST_CHUNK, encode_chunk};
 use crate::core::cors::CorsPolicy;
+use crate::core::error_pages::ErrorPages;
 use crate::core::handler::Handler;
-use crate::core::request::{Request, handle_request_sync};
+use crate::core::limits::ServerLimits;
+use crate::core::middleware::{Dispatcher, Middleware};
+use crate::core::request::{Request, error_response};
 use crate::core::request_handler::Rh;
 use crate::core::request_type::Rt;
 use crate::core::response::Response;
-use crate::runtime::shared::print_server_info;
+use crate::core::router::{RouteError, RouteInfo, RouteTree, Router, UrlError};
+use crate::core::state::SharedState;
+use crate::core::status_code::StatusCode;
+use crate::runtime::shared::{DEFAULT_IDLE_TIMEOUT, DEFAULT_MAX_REQUESTS_PER_CONNECTION, print_server_info};
 use crate::runtime::sync::threadpool::ThreadPool;
 use std::collections::HashMap;
+use std::io::BufReader;
 use std::io::prelude::Write;
 use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Counts a connection as open from the moment it is accepted until its worker is done with it.
+struct OpenConnection(Arc<AtomicUsize>);
+
+impl OpenConnection {
+  fn new(open: &Arc<AtomicUsize>) -> Self {
+    open.fetch_add(1, Ordering::SeqCst);
+    OpenConnection(Arc::clone(open))
+  }
+
+  /// Whether more connections are open than there are workers, so some wait for one.
+  fn others_waiting(&self, workers: usize) -> bool {
+    self.0.load(Ordering::SeqCst) > workers
+  }
+}
+
+impl Drop for OpenConnection {
+  fn drop(&mut self) {
+    self.0.fetch_sub(1, Ordering::SeqCst);
+  }
+}
 
 pub struct Server {
   url: String,
   listener: TcpListener,
   pool: Arc<Mutex<ThreadPool>>,
-  routes: HashMap<(Rt, String), Rh>,
+  routes: Arc<RouteTree>,
   files_sources: Vec<String>,
   auto_close: bool,
+  idle_timeout: Duration,
+  max_requests: usize,
+  limits: ServerLimits,
   cors: Option<Arc<CorsPolicy>>,
+  error_pages: Arc<ErrorPages>,
+  middleware: Vec<Arc<dyn Middleware>>,
+  state: Arc<SharedState>,
 }
 
 impl Server {
+  /// Creates a new server with `pool_size` worker threads, each serving one connection at a
+  /// time, and binds to the specified URL.
+  /// Fails with `InvalidInput` when a route of `routes_list` cannot be added (see `RouteError`).
   pub fn new(
     serving_url: &str,
     pool_size: u8,
     routes_list: Option<HashMap<(Rt, String), Rh>>,
   ) -> Result<Server, std::io::Error> {
+    let routes = Arc::new(RouteTree::try_from_routes(routes_list.unwrap_or_default())?);
     let listener = TcpListener::bind(serving_url)?;
     let url = listener.local_addr()?.to_string();
     let pool = Arc::new(Mutex::new(ThreadPool::new(pool_size as usize)));
-    let routes = routes_list.unwrap_or_default();
 
     Ok(Server {
       url,
@@ -43,14 +83,44 @@ impl Server {
       routes,
       files_sources: Vec::new(),
       auto_close: true,
+      idle_timeout: DEFAULT_IDLE_TIMEOUT,
+      max_requests: DEFAULT_MAX_REQUESTS_PER_CONNECTION,
+      limits: ServerLimits::default(),
       cors: Some(Arc::new(CorsPolicy::default())),
+      error_pages: Arc::new(ErrorPages::new()),
+      middleware: Vec::new(),
+      state: Arc::new(SharedState::new()),
     })
   }
 
+  /// When `true` (the default) every connection is closed after one response;
+  /// `false` keeps HTTP/1.1 connections open for further requests.
   pub fn set_auto_close(&mut self, state: bool) {
     self.auto_close = state;
   }
 
+  /// How long a kept-alive connection may sit without a new request before it is closed.
+  /// A zero timeout closes every connection after its first response.
+  ///
+  /// Each open connection keeps one of the `pool_size` worker threads busy. While other
+  /// connections wait for a worker, a kept-alive connection is closed after its current
+  /// response; one already waiting for its next request still holds its worker for up to
+  /// this timeout.
+  pub fn set_idle_timeout(&mut self, timeout: Duration) {
+    self.idle_timeout = timeout;
+  }
+
+  /// Number of requests served on one connection before it is closed (minimum 1).
+  pub fn set_max_requests_per_connection(&mut self, max: usize) {
+    self.max_requests = max.max(1);
+  }
+
+  /// Replaces the timeouts and size caps applied while reading requests.
+  pub fn set_limits(&mut self, limits: ServerLimits) {
+    self.limits = limits;
+  }
+
+  /// Replaces the CORS policy, which runs as the outermost global middleware.
   pub fn set_cors(&mut self, policy: CorsPolicy) {
     self.cors = Some(Arc::new(policy));
   }
@@ -59,6 +129,46 @@ impl Server {
     self.set_cors(CorsPolicy::from_config_str(config));
   }
 
+  /// Runs `middleware` around every request the server parses, routed or not. Global
+  /// middleware runs in the order it was added, inside the CORS policy and outside any route's
+  /// own middleware.
+  pub fn add_middleware<M: Middleware + 'static>(&mut self, middleware: M) {
+    self.middleware.push(Arc::new(middleware));
+  }
+
+  /// Shares `state` with every handler, which reads it with `request.state::<T>()`. Each
+  /// type holds one value; giving another value of the same type replaces it.
+  pub fn with_state<T: Send + Sync + 'static>(&mut self, state: T) {
+    Arc::get_mut(&mut self.state).unwrap().insert(state);
+  }
+
+  /// Handles requests that match no route and no static file, which otherwise get a 404.
+  pub fn set_fallback(&mut self, handler: Arc<dyn Handler>) {
+    Arc::get_mut(&mut self.error_pages).unwrap().set_fallback(handler);
+  }
+
+  /// Renders the error responses with `status` that the server builds itself: unmatched
+  /// requests without a fallback, 405 answers and requests the parser refuses.
+  pub fn set_error_renderer<F>(&mut self, status: StatusCode, renderer: F)
+  where
+    F: Fn(&Request, StatusCode) -> Response + Send + Sync + 'static,
+  {
+    Arc::get_mut(&mut self.error_pages)
+      .unwrap()
+      .set_renderer(status, Arc::new(renderer));
+  }
+
+  /// Like `set_error_renderer`, for every 4xx and 5xx status without a renderer of its own,
+  /// e.g. `problem_json`.
+  pub fn set_default_error_renderer<F>(&mut self, renderer: F)
+  where
+    F: Fn(&Request, StatusCode) -> Response + Send + Sync + 'static,
+  {
+    Arc::get_mut(&mut self.error_pages)
+      .unwrap()
+      .set_default_renderer(Arc::new(renderer));
+  }
+
   pub fn url(&self) -> &str {
     self.url.as_str()
   }
@@ -67,9 +177,95 @@ impl Server {
     self.listener.local_addr()
   }
 
+  /// Adds a new route to the server.
+  ///
+  /// Panics when `try_add_route` would fail.
   pub fn add_route(&mut self, path: &str, rt: Rt, handler: Arc<dyn Handler>) {
-    let key = (rt, path.to_string());
-    self.routes.insert(key, Rh { handler });
+    self
+      .try_add_route(path, rt, handler)
+      .unwrap_or_else(|err| panic!("{}", err));
+  }
+
+  /// Adds a route, failing when `path` is malformed or another route for `rt` already matches
+  /// the same paths.
+  pub fn try_add_route(&mut self, path: &str, rt: Rt, handler: Arc<dyn Handler>) -> Result<(), RouteError> {
+    Arc::get_mut(&mut self.routes)
+      .unwrap()
+      .try_insert(rt, path, Rh { handler })
+  }
+
+  /// Adds a route whose handler runs through `middleware`, outermost first.
+  ///
+  /// Panics like `add_route`.
+  pub fn add_route_with(
+    &mut self,
+    path: &str,
+    rt: Rt,
+    handler: Arc<dyn Handler>,
+    middleware: Vec<Arc<dyn Middleware>>,
+  ) {
+    self
+      .try_add_route_with(path, rt, handler, middleware)
+      .unwrap_or_else(|err| panic!("{}", err));
+  }
+
+  /// Like `add_route_with`, returning the `RouteError` instead of panicking.
+  pub fn try_add_route_with(
+    &mut self,
+    path: &str,
+    rt: Rt,
+    handler: Arc<dyn Handler>,
+    middleware: Vec<Arc<dyn Middleware>>,
+  ) -> Result<(), RouteError> {
+    Arc::get_mut(&mut self.routes)
+      .unwrap()
+      .try_insert_with(rt, path, Rh { handler }, middleware)
+  }
+
+  /// Adds a route that `url_for` can build links to by `name`.
+  ///
+  /// Panics like `add_route`, or when `name` is already taken.
+  pub fn add_named_route(&mut self, name: &str, path: &str, rt: Rt, handler: Arc<dyn Handler>) {
+    self
+      .try_add_named_route(name, path, rt, handler)
+      .unwrap_or_else(|err| panic!("{}", err));
+  }
+
+  /// Like `add_named_route`, returning the `RouteError` instead of panicking. Nothing is added
+  /// when the route or its name is refused.
+  pub fn try_add_named_route(
+    &mut self,
+    name: &str,
+    path: &str,
+    rt: Rt,
+    handler: Arc<dyn Handler>,
+  ) -> Result<(), RouteError> {
+    Arc::get_mut(&mut self.routes)
+      .unwrap()
+      .try_insert_named(name, rt, path, Rh { handler }, Vec::new())
+  }
+
+  /// Builds the path of the route registered as `name`, see `RouteTree::url_for`.
+  pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Result<String, UrlError> {
+    self.routes.url_for(name, params)
+  }
+
+  /// Adds every route of `router` under `prefix`, e.g. `/api/v1`.
+  ///
+  /// Panics when one of them cannot be added, see `add_named_route`.
+  pub fn nest(&mut self, prefix: &str, router: Router) {
+    self.try_nest(prefix, router).unwrap_or_else(|err| panic!("{}", err));
+  }
+
+  /// Like `nest`, returning the `RouteError` instead of panicking. On failure none of the
+  /// routes of `router` are added.
+  pub fn try_nest(&mut self, prefix: &str, router: Router) -> Result<(), RouteError> {
+    Arc::get_mut(&mut self.routes).unwrap().try_mount(prefix, router)
+  }
+
+  /// Every registered route with its method, pattern and name, in the order they were added.
+  pub fn routes(&self) -> &[RouteInfo] {
+    self.routes.routes()
   }
 
   pub fn add_files_source<S>(&mut self, base: S)
@@ -86,46 +282,57 @@ impl Server {
 
   pub fn run(&self) {
     print_server_info(self.listener.local_addr().unwrap(), self.auto_close);
+    let dispatcher = Arc::new(Dispatcher::new(
+      &self.routes,
+      &self.files_sources,
+      &self.error_pages,
+      self.cors.as_ref(),
+      &self.middleware,
+      &self.state,
+    ));
+    let workers = self.pool.lock().unwrap().size();
+    let open = Arc::new(AtomicUsize::new(0));
     for stream in self.listener.incoming() {
       match stream {
         Ok(stream) => {
-          let routes_local = self.routes.clone();
-          let sources_local = self.files_sources.clone();
-          let close_flag = self.auto_close;
-          let cors_policy = self.cors.clone();
+          let connection = OpenConnection::new(&open);
+          let dispatcher = Arc::clone(&dispatcher);
+          let close_flag = self.auto_close || self.idle_timeout.is_zero();
+          let idle_timeout = self.idle_timeout;
+          let max_requests = self.max_requests;
+          let limits = self.limits;
           let pool = Arc::clone(&self.pool);
           pool.lock().unwrap().run(move || {
-            let (mut request, early_resp) = Request::parse_stream_sync(&stream, &routes_local, &sources_local);
-            let origin = request.origin().map(str::to_string);
-          let method = request.method.clone();
-          let answer = if let Some(resp) = early_resp {
-            Some(resp)
-          } else {
-            let routed = handle_request_sync(&mut request, &routes_local, &sources_local);
-            if routed.is_none()
-              && method == crate::core::request_type::RequestType::OPTIONS
-              && cors_policy.is_some()
-            {
-              Some(Self::preflight_response(cors_policy.as_deref()))
+            let mut reader = BufReader::new(&stream);
+            let first_line_timeout = if close_flag {
+              limits.header_read_timeout
             } else {
-              routed
-            }
-          };
-            match answer {
-              Some(response) => Self::send_response(
-                stream,
-                &response,
-                close_flag,
-                cors_policy.as_deref(),
-                origin.as_deref(),
-              ),
-              None => Self::send_response(
-                stream,
-                &Response::new(),
-                close_flag,
-                cors_policy.as_deref(),
-                origin.as_deref(),
-              ),
+              idle_timeout
+            };
+            let mut served = 0;
+            loop {
+              let Some((mut request, early_resp)) =
+                Request::parse_stream_sync(&mut reader, &limits, first_line_timeout, close_flag)
+              else {
+                if served == 0 {
+                  let response = error_response(StatusCode::BadRequest);
+                  Self::send_response(&stream, dispatcher.refuse(&Request::default(), response), true, false);
+                }
+                break;
+              };
+              served += 1;
+              let method = request.method.clone();
+              let answer = match early_resp {
+                Some(resp) => dispatcher.refuse(&request, resp),
+                None => futures::executor::block_on(dispatcher.handle(&mut request)),
+              };
+              // A kept-alive connection gives its worker up to connections waiting for one
+              let close =
+                close_flag || !request.keep_alive() || served >= max_requests || connection.others_waiting(workers);
+              Self::send_response(&stream, answer, close, method == Rt::HEAD);
+              if close {
+                break;
+              }
             }
           });
         }
@@ -141,41 +348,39 @@ impl Server {
     pool.stop();
   }
 
-  fn preflight_response(cors: Option<&CorsPolicy>) -> Response {
-    if let Some(policy) = cors {
-      return policy.preflight_response();
-    }
-    Response::new()
-  }
-
-  fn send_response(
-    mut stream: TcpStream,
-    response: &Response,
-    close: bool,
-    cors: Option<&CorsPolicy>,
-    origin: Option<&str>,
-  ) {
+  fn send_response(mut stream: &TcpStream, mut response: Response, close: bool, head_request: bool) {
+    let body_stream = response.stream.take();
     let connection_header = if close { "Connection: close\r\n" } else { "" };
+    let length_header = match body_stream {
+      Some(_) => "Transfer-Encoding: chunked\r\n".to_string(),
+      None => format!("Content-Length: {}\r\n", response.content.len()),
+    };
     let mut header = format!(
-      "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}",
-      response.status,
-      response.content_type,
-      response.content.len(),
+      "{}{}{}{}",
+      response.status_line(),
+      response.content_type_line(),
+      length_header,
       connection_header
     );
-    if let Some(policy) = cors {
-      for (k, v) in policy.header_lines(origin) {
-        header.push_str(&format!("{}: {}\r\n", k, v));
-      }
-    }
+    header.push_str(&response.header_lines());
     header.push_str("\r\n");
     let _ = stream.write_all(header.as_bytes());
 
-    if response.content_type.starts_with("image/") {
-      let _ = stream.write_all(&response.content);
-    } else {
-      let text = String::from_utf8_lossy(&response.content);
-      let _ = stream.write_all(text.as_bytes());
+    match body_stream {
+      // a HEAD response carries the headers of the GET response and no body
+      _ if head_request => {}
+      Some(body) => {
+        let pieces = std::iter::once(response.content).chain(body.into_blocking_iter());
+        for piece in pieces.filter(|p| !p.is_empty()) {
+          if stream.write_all(&encode_chunk(&piece)).is_err() {
+            break;
+          }
+        }
+        let _ = stream.write_all(LAST_CHUNK);
+      }
+      None => {
+        let _ = stream.write_all(&response.content);
+      }
     }
 
     let _ = stream.flush();
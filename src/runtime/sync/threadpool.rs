@@ -73,6 +73,11 @@ impl ThreadPool {
     ThreadPool { workers, sender }
   }
 
+  /// Number of worker threads.
+  pub fn size(&self) -> usize {
+    self.workers.len()
+  }
+
   pub fn run<F>(&self, _f: F)
   where
     F: FnOnce() + Send + 'static,
@@ -92,10 +97,10 @@ impl ThreadPool {
     }
 
     for worker in &mut self.workers {
-      if let Some(thread) = worker.thread.take() {
-        if let Err(_e) = thread.join() {
-          // println!("Error joining thread: {:?}", _e);
-        }
+      if let Some(thread) = worker.thread.take()
+        && let Err(_e) = thread.join()
+      {
+        // println!("Error joining thread: {:?}", _e);
       }
     }
   }
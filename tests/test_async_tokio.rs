@@ -1,11 +1,24 @@
 #![cfg(feature = "async_tokio")]
 
-use httpageboy::test_utils::{run_test, setup_test_server};
-use httpageboy::{Request, Response, Rt, Server, StatusCode, handler};
+use httpageboy::test_utils::{run_test, setup_test_server, test_server_url};
+use async_trait::async_trait;
+use httpageboy::{
+  CorsPolicy, Middleware, Next, Request, Response, Router, Rt, Server, ServerLimits, StatusCode, handler, problem_json,
+};
+use httpageboy::{IntoResponse, ParamError, Path, State, extract_handler};
+#[cfg(feature = "json")]
+use httpageboy::{Json, Query};
 use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
 
 const REGULAR_SERVER_URL: &str = "127.0.0.1:48080";
 const STRICT_SERVER_URL: &str = "127.0.0.1:48081";
+const KEEP_ALIVE_SERVER_URL: &str = "127.0.0.1:48082";
+const LIMITED_SERVER_URL: &str = "127.0.0.1:48083";
+const BRANDED_SERVER_URL: &str = "127.0.0.1:48084";
+const FALLBACK_SERVER_URL: &str = "127.0.0.1:48085";
+const NO_IDLE_SERVER_URL: &str = "127.0.0.1:48086";
 
 async fn common_server_definition(server_url: &str) -> Server {
   let mut server = match Server::new(server_url, None).await {
@@ -26,8 +39,70 @@ async fn common_server_definition(server_url: &str) -> Server {
   server.add_route("/test", Rt::OPTIONS, handler!(demo_handle_options));
   server.add_route("/test", Rt::CONNECT, handler!(demo_handle_connect));
   server.add_route("/test", Rt::TRACE, handler!(demo_handle_trace));
+  server.add_route("/binary", Rt::POST, handler!(demo_handle_binary));
+  #[cfg(feature = "json")]
+  server.add_route("/json", Rt::POST, handler!(demo_handle_json));
+  server.add_route("/echo", Rt::POST, handler!(demo_handle_echo));
+  server.add_route("/report.csv", Rt::GET, handler!(demo_handle_csv_iter));
+  server.add_route("/report-stream.csv", Rt::GET, handler!(demo_handle_csv_stream));
+  server.add_route("/created", Rt::GET, handler!(demo_handle_created));
+  #[cfg(feature = "json")]
+  server.add_route("/created-json", Rt::GET, handler!(demo_handle_created_json));
+  server.add_route("/custom-status", Rt::GET, handler!(demo_handle_custom_status));
+  server.add_route("/items/{*rest}", Rt::GET, handler!(demo_handle_item_rest));
+  server.add_route("/items/{id}", Rt::GET, handler!(demo_handle_item));
+  server.add_route("/items/special", Rt::GET, handler!(demo_handle_special_item));
+  server.add_route("/assets/{*path}", Rt::GET, handler!(demo_handle_asset));
+  server.add_route("/files/{name}.{ext}", Rt::GET, handler!(demo_handle_file));
+  server.add_route("/v1/users/{id}", Rt::GET, handler!(demo_handle_user));
+  server.add_route("/v1/users/{id}.json", Rt::GET, handler!(demo_handle_user_json));
+  server.add_named_route("posts", "/posts/{page?}", Rt::GET, handler!(demo_handle_posts));
+  server.add_route("/orders/{slug:[a-z-]+}", Rt::GET, handler!(demo_handle_order_slug));
+  server.add_route("/orders/{uuid:uuid}", Rt::GET, handler!(demo_handle_order_uuid));
+  server.add_named_route("order", "/orders/{id:u64}", Rt::GET, handler!(demo_handle_order));
+  server.add_route("/reports/{year:u16}.csv", Rt::GET, handler!(demo_handle_report_year));
+  server.add_route("/typed/{id}", Rt::GET, handler!(demo_handle_typed));
+  let admin = Router::new()
+    .route("/stats", Rt::GET, handler!(demo_handle_group_stats))
+    .middleware(RequireToken);
+  let api = Router::new()
+    .route("/users", Rt::GET, handler!(demo_handle_group_users))
+    .named_route("api_user", "/users/{id}", Rt::GET, handler!(demo_handle_group_user))
+    .nest("/admin", admin)
+    .middleware(GroupTag("v1"))
+    .cors(CorsPolicy::from_config_str("origin=http://app.example"));
+  server.nest("/api/v1", api);
+  server.add_named_route("person", "/people/{id}", Rt::GET, handler!(demo_handle_person));
+  server.add_named_route("static_file", "/static/{*path}", Rt::GET, handler!(demo_handle_asset));
+  server.add_route("/signup", Rt::POST, handler!(demo_handle_signup));
+  server.add_route("/links", Rt::GET, handler!(demo_handle_links));
+  server.add_route_with("/guarded", Rt::GET, handler!(demo_handle_get), vec![Arc::new(RequireToken)]);
+  server.add_route("/greeting", Rt::GET, handler!(demo_handle_greeting));
+  server.with_state(AppConfig {
+    greeting: "hello from state",
+  });
+  server.add_route_with("/request-id", Rt::GET, handler!(demo_handle_request_id), vec![Arc::new(TagRequest)]);
+  server.add_route("/extract/users/{id}", Rt::GET, extract_handler!(demo_extract_user));
+  server.add_route("/extract/teams/{team}/members/{member}", Rt::GET, extract_handler!(demo_extract_member));
+  #[cfg(feature = "json")]
+  server.add_route("/extract/groups/{id}/users", Rt::POST, extract_handler!(demo_extract_create));
+  server.add_route("/accounts/{id}", Rt::GET, handler!(demo_handle_account));
+  server.add_route("/nicknames/{id}", Rt::GET, handler!(demo_handle_nickname));
+  server.add_route("/queued", Rt::POST, handler!(demo_handle_queued));
+  server.add_route("/ping", Rt::GET, handler!(demo_handle_ping));
+  let partners = Router::new()
+    .route("/users", Rt::GET, handler!(demo_handle_group_users))
+    .cors(CorsPolicy::from_config_str("origin=https://app.example,max_age=off"));
+  server.nest("/partners", partners);
+  server.add_route("/trailers", Rt::POST, handler!(demo_handle_trailers));
+  server.add_route("/invalid-status/{kind}", Rt::GET, handler!(demo_handle_invalid_status));
+  server.add_route("/split-content-type", Rt::GET, handler!(demo_handle_split_content_type));
+  server.add_route("/unicode-header", Rt::GET, handler!(demo_handle_unicode_header));
+  server.add_route("/uploads/{*name}", Rt::POST, handler!(demo_handle_post));
+  server.add_route("/pushed-type", Rt::GET, handler!(demo_handle_pushed_type));
   let res_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("res");
   server.add_files_source(res_path.to_str().unwrap());
+  server.add_files_source(mime_fixtures_dir());
   server
 }
 
@@ -39,110 +114,694 @@ async fn strict_server_definition() -> Server {
   common_server_definition(STRICT_SERVER_URL).await
 }
 
+async fn keep_alive_server_definition() -> Server {
+  let mut server = common_server_definition(KEEP_ALIVE_SERVER_URL).await;
+  server.set_auto_close(false);
+  server.set_idle_timeout(std::time::Duration::from_millis(300));
+  server.set_max_requests_per_connection(3);
+  server
+}
+
+async fn no_idle_server_definition() -> Server {
+  let mut server = common_server_definition(NO_IDLE_SERVER_URL).await;
+  server.set_auto_close(false);
+  server.set_idle_timeout(Duration::ZERO);
+  server
+}
+
+async fn limited_server_definition() -> Server {
+  let mut server = common_server_definition(LIMITED_SERVER_URL).await;
+  server.set_limits(ServerLimits {
+    header_read_timeout: Duration::from_millis(150),
+    body_read_timeout: Duration::from_millis(150),
+    max_header_bytes: 512,
+    max_header_count: 4,
+    max_body_size: 16,
+    max_uri_length: 32,
+  });
+  server
+}
+
 async fn create_test_server() -> Server {
   regular_server_definition().await
 }
 
 async fn boot_regular() {
-  setup_test_server(Some(REGULAR_SERVER_URL), || create_test_server()).await;
+  setup_test_server(Some(REGULAR_SERVER_URL), create_test_server).await;
 }
 
 async fn boot_strict() {
-  setup_test_server(Some(STRICT_SERVER_URL), || strict_server_definition()).await;
+  setup_test_server(Some(STRICT_SERVER_URL), strict_server_definition).await;
+}
+
+async fn boot_keep_alive() {
+  setup_test_server(Some(KEEP_ALIVE_SERVER_URL), keep_alive_server_definition).await;
 }
 
 async fn run_regular(request: &[u8], expected: &[u8]) -> String {
   run_test(request, expected, Some(REGULAR_SERVER_URL)).await
 }
 
+async fn run_keep_alive(request: &[u8], expected: &[u8]) -> String {
+  run_test(request, expected, Some(KEEP_ALIVE_SERVER_URL)).await
+}
+
+async fn boot_no_idle() {
+  setup_test_server(Some(NO_IDLE_SERVER_URL), no_idle_server_definition).await;
+}
+
+async fn boot_limited() {
+  setup_test_server(Some(LIMITED_SERVER_URL), limited_server_definition).await;
+}
+
+async fn run_limited(request: &[u8], expected: &[u8]) -> String {
+  run_test(request, expected, Some(LIMITED_SERVER_URL)).await
+}
+
 async fn run_strict(request: &[u8], expected: &[u8]) -> String {
   run_test(request, expected, Some(STRICT_SERVER_URL)).await
 }
 
+async fn branded_server_definition() -> Server {
+  let mut server = common_server_definition(BRANDED_SERVER_URL).await;
+  server.set_default_error_renderer(problem_json);
+  server.set_error_renderer(StatusCode::MethodNotAllowed, branded_error_page);
+  server.add_middleware(GroupTag("branded"));
+  server
+}
+
+async fn fallback_server_definition() -> Server {
+  let mut server = common_server_definition(FALLBACK_SERVER_URL).await;
+  server.set_fallback(handler!(demo_handle_fallback));
+  server.set_default_error_renderer(problem_json);
+  server
+}
+
+async fn boot_branded() {
+  setup_test_server(Some(BRANDED_SERVER_URL), branded_server_definition).await;
+}
+
+async fn run_branded(request: &[u8], expected: &[u8]) -> String {
+  run_test(request, expected, Some(BRANDED_SERVER_URL)).await
+}
+
+async fn boot_fallback() {
+  setup_test_server(Some(FALLBACK_SERVER_URL), fallback_server_definition).await;
+}
+
+async fn run_fallback(request: &[u8], expected: &[u8]) -> String {
+  run_test(request, expected, Some(FALLBACK_SERVER_URL)).await
+}
+
+const MIME_TYPES: [(&str, &str); 33] = [
+  ("png", "image/png"),
+  ("jpg", "image/jpeg"),
+  ("jpeg", "image/jpeg"),
+  ("gif", "image/gif"),
+  ("bmp", "image/bmp"),
+  ("svg", "image/svg+xml"),
+  ("webp", "image/webp"),
+  ("html", "text/html"),
+  ("css", "text/css"),
+  ("js", "application/javascript"),
+  ("json", "application/json"),
+  ("xml", "application/xml"),
+  ("pdf", "application/pdf"),
+  ("doc", "application/msword"),
+  ("docx", "application/msword"),
+  ("xls", "application/vnd.ms-excel"),
+  ("xlsx", "application/vnd.ms-excel"),
+  ("ppt", "application/vnd.ms-powerpoint"),
+  ("pptx", "application/vnd.ms-powerpoint"),
+  ("zip", "application/zip"),
+  ("rar", "application/x-rar-compressed"),
+  ("txt", "text/plain"),
+  ("csv", "text/csv"),
+  ("mp3", "audio/mpeg"),
+  ("wav", "audio/wav"),
+  ("mp4", "video/mp4"),
+  ("avi", "video/x-msvideo"),
+  ("mov", "video/quicktime"),
+  ("ogg", "audio/ogg"),
+  ("ogv", "video/ogg"),
+  ("oga", "audio/ogg"),
+  ("ico", "image/x-icon"),
+  ("bin", "application/octet-stream"),
+];
+
+fn binary_payload() -> Vec<u8> {
+  // Every byte value plus an embedded header terminator; deliberately not valid UTF-8
+  let mut payload: Vec<u8> = (0..=255u8).rev().collect();
+  payload.extend_from_slice(b"\r\n\r\n");
+  payload
+}
+
+fn mime_fixtures_dir() -> String {
+  let dir = std::env::temp_dir().join("httpageboy_mime_async_tokio");
+  std::fs::create_dir_all(&dir).expect("create fixtures dir");
+  for (ext, _) in MIME_TYPES {
+    std::fs::write(dir.join(format!("sample.{}", ext)), binary_payload()).expect("write fixture");
+  }
+  std::fs::create_dir_all(dir.join("uploads")).expect("create uploads dir");
+  std::fs::write(dir.join("uploads").join("report.txt"), "report").expect("write fixture");
+  dir.to_string_lossy().to_string()
+}
+
+async fn fetch_raw(request: &[u8]) -> Vec<u8> {
+  use tokio::io::{AsyncReadExt, AsyncWriteExt};
+  let mut stream = tokio::net::TcpStream::connect(test_server_url(REGULAR_SERVER_URL))
+    .await
+    .expect("connect to test server");
+  stream.write_all(request).await.expect("write request");
+  let _ = stream.shutdown().await;
+  let mut buf = Vec::new();
+  stream.read_to_end(&mut buf).await.expect("read response");
+  buf
+}
+
+fn open_keep_alive() -> std::net::TcpStream {
+  let stream = std::net::TcpStream::connect(test_server_url(KEEP_ALIVE_SERVER_URL)).expect("connect to test server");
+  stream
+    .set_read_timeout(Some(std::time::Duration::from_secs(2)))
+    .expect("set read timeout");
+  stream
+}
+
+fn exchange(stream: &mut std::net::TcpStream, request: &[u8]) -> String {
+  use std::io::{Read, Write};
+  stream.write_all(request).expect("write request");
+  // Read the head byte by byte so nothing past this response is consumed
+  let mut head = Vec::new();
+  let mut byte = [0u8; 1];
+  while !head.ends_with(b"\r\n\r\n") {
+    match stream.read(&mut byte) {
+      Ok(1) => head.push(byte[0]),
+      _ => break,
+    }
+  }
+  let head = String::from_utf8_lossy(&head).to_string();
+  let length = head
+    .lines()
+    .find_map(|line| line.strip_prefix("Content-Length: "))
+    .and_then(|value| value.trim().parse::<usize>().ok())
+    .unwrap_or(0);
+  let mut body = vec![0u8; length];
+  stream.read_exact(&mut body).expect("read response body");
+  head + &String::from_utf8_lossy(&body)
+}
+
+fn is_closed(stream: &mut std::net::TcpStream) -> bool {
+  use std::io::Read;
+  let mut rest = Vec::new();
+  matches!(stream.read_to_end(&mut rest), Ok(0))
+}
+
+fn fetch_stalled(request: &[u8]) -> String {
+  use std::io::{Read, Write};
+  // Leaves the write side open so the server has to give up on its own
+  let mut stream = std::net::TcpStream::connect(test_server_url(LIMITED_SERVER_URL)).expect("connect to test server");
+  stream
+    .set_read_timeout(Some(std::time::Duration::from_secs(2)))
+    .expect("set read timeout");
+  stream.write_all(request).expect("write request");
+  let mut buf = Vec::new();
+  let _ = stream.read_to_end(&mut buf);
+  String::from_utf8_lossy(&buf).to_string()
+}
+
 async fn demo_handle_home(_request: &Request) -> Response {
   Response {
-    status: StatusCode::Ok.to_string(),
+    status: StatusCode::Ok,
     content_type: String::new(),
     content: b"home".to_vec(),
+    ..Default::default()
   }
 }
 
 async fn demo_handle_post(_request: &Request) -> Response {
-  let mut ordered: BTreeMap<&String, &String> = BTreeMap::new();
-  for (k, v) in &_request.params {
-    ordered.insert(k, v);
-  }
+  let query: BTreeMap<&String, &String> = _request.params.iter().collect();
+  let path: BTreeMap<&String, &String> = _request.path_params.iter().collect();
   let body = format!(
-    "Method: {}\nUri: {}\nParams: {:?}\nBody: {:?}",
-    _request.method, _request.path, ordered, _request.body
+    "Method: {}\nUri: {}\nParams: {:?}\nPath params: {:?}\nBody: {:?}",
+    _request.method, _request.path, query, path, _request.text_lossy()
   );
   Response {
-    status: StatusCode::Ok.to_string(),
+    status: StatusCode::Ok,
     content_type: String::new(),
     content: body.into_bytes(),
+    ..Default::default()
   }
 }
 
 async fn demo_handle_get(_request: &Request) -> Response {
   Response {
-    status: StatusCode::Ok.to_string(),
+    status: StatusCode::Ok,
     content_type: String::new(),
     content: b"get".to_vec(),
+    ..Default::default()
   }
 }
 
 async fn demo_handle_put(_request: &Request) -> Response {
   let body = format!(
     "Method: {}\nUri: {}\nParams: {:?}\nBody: {:?}",
-    _request.method, _request.path, _request.params, _request.body
+    _request.method, _request.path, _request.params, _request.text_lossy()
   );
   Response {
-    status: StatusCode::Ok.to_string(),
+    status: StatusCode::Ok,
     content_type: String::new(),
     content: body.into_bytes(),
+    ..Default::default()
   }
 }
 
 async fn demo_handle_delete(_request: &Request) -> Response {
   Response {
-    status: StatusCode::Ok.to_string(),
+    status: StatusCode::Ok,
     content_type: String::new(),
     content: b"delete".to_vec(),
+    ..Default::default()
   }
 }
 
 async fn demo_handle_head(_request: &Request) -> Response {
   Response {
-    status: StatusCode::Ok.to_string(),
+    status: StatusCode::Ok,
     content_type: String::new(),
     content: b"head".to_vec(),
+    ..Default::default()
   }
 }
 
 async fn demo_handle_options(_request: &Request) -> Response {
   Response {
-    status: StatusCode::Ok.to_string(),
+    status: StatusCode::Ok,
     content_type: String::new(),
     content: b"options".to_vec(),
+    ..Default::default()
   }
 }
 
 async fn demo_handle_connect(_request: &Request) -> Response {
   Response {
-    status: StatusCode::Ok.to_string(),
+    status: StatusCode::Ok,
     content_type: String::new(),
     content: b"connect".to_vec(),
+    ..Default::default()
   }
 }
 
 async fn demo_handle_trace(_request: &Request) -> Response {
   Response {
-    status: StatusCode::Ok.to_string(),
+    status: StatusCode::Ok,
     content_type: String::new(),
     content: b"trace".to_vec(),
+    ..Default::default()
+  }
+}
+
+async fn demo_handle_binary(_request: &Request) -> Response {
+  Response {
+    status: StatusCode::Ok,
+    content_type: String::new(),
+    content: format!("Bytes: {:?}", _request.body).into_bytes(),
+    ..Default::default()
+  }
+}
+
+#[cfg(feature = "json")]
+#[derive(serde::Deserialize)]
+struct Greeting {
+  name: String,
+}
+
+#[cfg(feature = "json")]
+async fn demo_handle_json(_request: &Request) -> Response {
+  match _request.json::<Greeting>() {
+    Ok(greeting) => Response {
+      status: StatusCode::Ok,
+      content_type: String::new(),
+      content: format!("Hello, {}", greeting.name).into_bytes(),
+      ..Default::default()
+    },
+    Err(_) => Response {
+      status: StatusCode::BadRequest,
+      content_type: String::new(),
+      content: Vec::new(),
+      ..Default::default()
+    },
+  }
+}
+
+async fn demo_handle_echo(_request: &Request) -> Response {
+  Response {
+    status: StatusCode::Ok,
+    content_type: "application/octet-stream".to_string(),
+    content: _request.body.clone(),
+    ..Default::default()
+  }
+}
+
+async fn demo_handle_csv_iter(_request: &Request) -> Response {
+  let rows = (0..3).map(|i| format!("row{}\n", i).into_bytes());
+  Response::from_chunks(StatusCode::Ok, "text/csv", rows)
+}
+
+async fn demo_handle_csv_stream(_request: &Request) -> Response {
+  let rows = futures::stream::iter((0..3).map(|i| format!("row{}\n", i).into_bytes()));
+  Response::from_stream(StatusCode::Ok, "text/csv", rows)
+}
+
+async fn demo_handle_created(_request: &Request) -> Response {
+  Response::builder()
+    .status(StatusCode::Created)
+    .header("Location", "/test/42")
+    .header("Set-Cookie", "a=1")
+    .header("Set-Cookie", "b=2")
+    .header("X-Injected", "bad\r\nEvil: 1")
+    .text("created")
+}
+
+#[cfg(feature = "json")]
+async fn demo_handle_created_json(_request: &Request) -> Response {
+  Response::builder()
+    .status(StatusCode::Created)
+    .header("Cache-Control", "no-store")
+    .json(&serde_json::json!({ "id": 42 }))
+}
+
+async fn demo_handle_custom_status(_request: &Request) -> Response {
+  Response::builder()
+    .status(StatusCode::Custom(599, "Network Read Timeout"))
+    .text("custom")
+}
+
+async fn demo_handle_item(_request: &Request) -> Response {
+  Response::builder().text(format!("item {}", _request.path_params["id"]))
+}
+
+async fn demo_handle_special_item(_request: &Request) -> Response {
+  Response::builder().text("special item")
+}
+
+async fn demo_handle_item_rest(_request: &Request) -> Response {
+  Response::builder().text(format!("rest {}", _request.path_params["rest"]))
+}
+
+async fn demo_handle_asset(_request: &Request) -> Response {
+  Response::builder().text(format!("asset {}", _request.path_params["path"]))
+}
+
+async fn demo_handle_file(_request: &Request) -> Response {
+  Response::builder().text(format!(
+    "file {} {}",
+    _request.path_params["name"], _request.path_params["ext"]
+  ))
+}
+
+async fn demo_handle_user(_request: &Request) -> Response {
+  Response::builder().text(format!("user {}", _request.path_params["id"]))
+}
+
+async fn demo_handle_user_json(_request: &Request) -> Response {
+  Response::builder().text(format!("user json {}", _request.path_params["id"]))
+}
+
+async fn demo_handle_posts(_request: &Request) -> Response {
+  let page = _request.path_params.get("page").map_or("none", String::as_str);
+  Response::builder().text(format!("posts page {}", page))
+}
+
+async fn demo_handle_order(_request: &Request) -> Response {
+  match _request.param::<u64>("id") {
+    Ok(id) => Response::builder().text(format!("order {}", id + 1)),
+    Err(err) => err.into(),
+  }
+}
+
+async fn demo_handle_order_slug(_request: &Request) -> Response {
+  Response::builder().text(format!("order slug {}", _request.path_params["slug"]))
+}
+
+async fn demo_handle_order_uuid(_request: &Request) -> Response {
+  Response::builder().text(format!("order uuid {}", _request.path_params["uuid"]))
+}
+
+async fn demo_handle_typed(_request: &Request) -> Response {
+  match _request.param::<u64>("id") {
+    Ok(id) => Response::builder().text(format!("typed {}", id)),
+    Err(err) => err.into(),
+  }
+}
+
+async fn demo_handle_report_year(_request: &Request) -> Response {
+  Response::builder().text(format!("report year {}", _request.path_params["year"]))
+}
+
+async fn demo_handle_group_users(_request: &Request) -> Response {
+  Response::builder().text("group users")
+}
+
+async fn demo_handle_group_user(_request: &Request) -> Response {
+  Response::builder().text(format!("group user {}", _request.path_params["id"]))
+}
+
+async fn demo_handle_group_stats(_request: &Request) -> Response {
+  Response::builder().text("admin stats")
+}
+
+struct GroupTag(&'static str);
+
+#[async_trait]
+impl Middleware for GroupTag {
+  async fn call(&self, request: &mut Request, next: Next<'_>) -> Response {
+    let mut response = next.run(request).await;
+    response.headers.push(("X-Group".to_string(), self.0.to_string()));
+    response
+  }
+}
+
+struct RequireToken;
+
+#[async_trait]
+impl Middleware for RequireToken {
+  async fn call(&self, request: &mut Request, next: Next<'_>) -> Response {
+    let authorized = request
+      .headers
+      .iter()
+      .any(|(k, v)| k.eq_ignore_ascii_case("x-token") && v == "secret");
+    if !authorized {
+      return Response::builder().status(StatusCode::Unauthorized).text("token required");
+    }
+    next.run(request).await
+  }
+}
+
+async fn demo_handle_person(_request: &Request) -> Response {
+  Response::builder().text(format!("person {}", _request.path_params["id"]))
+}
+
+async fn demo_handle_signup(_request: &Request) -> Response {
+  match _request.url_for("person", &[("id", "42")]) {
+    Ok(location) => Response::builder()
+      .status(StatusCode::Created)
+      .header("Location", &location)
+      .text("created"),
+    Err(err) => Response::builder().status(StatusCode::InternalServerError).text(err.to_string()),
+  }
+}
+
+async fn demo_handle_links(_request: &Request) -> Response {
+  let links = [
+    _request.url_for("api_user", &[("id", "7")]),
+    _request.url_for("person", &[("id", "ä/x y")]),
+    _request.url_for("static_file", &[("path", "css/a b.css")]),
+    _request.url_for("posts", &[]),
+    _request.url_for("posts", &[("page", "2")]),
+    _request.url_for("person", &[]),
+    _request.url_for("order", &[("id", "abc")]),
+    _request.url_for("nowhere", &[]),
+  ];
+  let lines: Vec<String> = links
+    .iter()
+    .map(|link| match link {
+      Ok(url) => url.clone(),
+      Err(err) => format!("error: {}", err),
+    })
+    .collect();
+  Response::builder().text(lines.join("\n"))
+}
+
+fn branded_error_page(_request: &Request, status: StatusCode) -> Response {
+  Response::builder()
+    .content_type("text/html")
+    .body(format!("<h1>Acme</h1><p>{}</p>", status))
+}
+
+async fn demo_handle_fallback(request: &Request) -> Response {
+  Response::builder()
+    .status(StatusCode::NotFound)
+    .text(format!("nothing at {}", request.path))
+}
+
+#[derive(Clone)]
+struct AppConfig {
+  greeting: &'static str,
+}
+
+async fn demo_handle_greeting(request: &Request) -> Response {
+  match request.state::<AppConfig>() {
+    Some(config) => Response::builder().text(format!("{}; counter: {:?}", config.greeting, request.state::<u64>())),
+    None => Response::builder()
+      .status(StatusCode::InternalServerError)
+      .text("no state"),
+  }
+}
+
+struct RequestId(String);
+
+struct ServedBy(&'static str);
+
+struct TagRequest;
+
+#[async_trait]
+impl Middleware for TagRequest {
+  async fn call(&self, request: &mut Request, next: Next<'_>) -> Response {
+    request.extensions.insert(RequestId("req-42".to_string()));
+    let mut response = next.run(request).await;
+    if let Some(by) = response.extensions.get::<ServedBy>().map(|served| served.0) {
+      response.headers.push(("X-Served-By".to_string(), by.to_string()));
+    }
+    response
+  }
+}
+
+async fn demo_handle_request_id(request: &Request) -> Response {
+  let id = request.extensions.get::<RequestId>().map_or("none", |id| id.0.as_str());
+  Response::builder()
+    .extension(ServedBy("request-id handler"))
+    .text(format!("request {}", id))
+}
+
+async fn demo_extract_user(Path(id): Path<u64>, State(config): State<AppConfig>) -> Response {
+  Response::builder().text(format!("{}: user {}", config.greeting, id))
+}
+
+async fn demo_extract_member(Path((team, member)): Path<(String, u32)>) -> Response {
+  Response::builder().text(format!("{} member {}", team, member))
+}
+
+#[cfg(feature = "json")]
+#[derive(serde::Deserialize)]
+struct Notify {
+  notify: Option<bool>,
+}
+
+#[cfg(feature = "json")]
+#[derive(serde::Deserialize)]
+struct NewMember {
+  name: String,
+  age: u8,
+}
+
+#[cfg(feature = "json")]
+async fn demo_extract_create(
+  Path(id): Path<u64>,
+  Query(query): Query<Notify>,
+  Json(body): Json<NewMember>,
+) -> Json<serde_json::Value> {
+  Json(serde_json::json!({
+    "group": id,
+    "name": body.name,
+    "age": body.age,
+    "notify": query.notify.unwrap_or(false),
+  }))
+}
+
+enum AccountError {
+  BadId(ParamError),
+  Missing,
+  Locked,
+}
+
+impl From<ParamError> for AccountError {
+  fn from(err: ParamError) -> Self {
+    AccountError::BadId(err)
+  }
+}
+
+impl IntoResponse for AccountError {
+  fn into_response(self) -> Response {
+    match self {
+      AccountError::BadId(err) => err.into_response(),
+      AccountError::Missing => (StatusCode::NotFound, "no such account").into_response(),
+      AccountError::Locked => (StatusCode::Forbidden, "account locked").into_response(),
+    }
   }
 }
 
+fn find_account(id: u64) -> Result<&'static str, AccountError> {
+  match id {
+    1 => Ok("ana"),
+    2 => Err(AccountError::Locked),
+    _ => Err(AccountError::Missing),
+  }
+}
+
+async fn demo_handle_account(request: &Request) -> Result<String, AccountError> {
+  let id = request.param::<u64>("id")?;
+  let name = find_account(id)?;
+  Ok(format!("account {}", name))
+}
+
+async fn demo_handle_nickname(request: &Request) -> Option<&'static str> {
+  match request.path_params["id"].as_str() {
+    "1" => Some("nana"),
+    _ => None,
+  }
+}
+
+async fn demo_handle_queued(request: &Request) -> (StatusCode, Vec<u8>) {
+  (StatusCode::Accepted, request.body.clone())
+}
+
+async fn demo_handle_ping(_request: &Request) -> StatusCode {
+  StatusCode::NoContent
+}
+
+async fn demo_handle_trailers(request: &Request) -> Response {
+  let authorized = request.headers.iter().any(|(k, _)| k.eq_ignore_ascii_case("authorization"));
+  let trailers: Vec<String> = request.trailers.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+  Response::builder().text(format!("authorization header: {}; trailers: {}", authorized, trailers.join(",")))
+}
+
+async fn demo_handle_invalid_status(_request: &Request) -> Response {
+  let status = match _request.path_params["kind"].as_str() {
+    "large" => StatusCode::Custom(1000, "Too Large"),
+    _ => StatusCode::Custom(299, "Fine\r\nSet-Cookie: session=1"),
+  };
+  Response::builder().status(status).text("custom")
+}
+
+async fn demo_handle_split_content_type(_request: &Request) -> Response {
+  Response::builder()
+    .header("Content-Type", "text/plain\r\nSet-Cookie: session=1")
+    .text("split")
+}
+
+async fn demo_handle_pushed_type(_request: &Request) -> Response {
+  let mut response = Response::builder().text("a,b");
+  response
+    .headers
+    .push(("content-type".to_string(), "text/csv".to_string()));
+  response
+}
+
+async fn demo_handle_unicode_header(_request: &Request) -> Response {
+  // "é" takes bytes 14 and 15, across the length of the `Access-Control-` prefix
+  Response::builder().header("Access-Controlé-Hint", "1").text("unicode")
+}
+
 #[tokio::test]
 async fn test_home() {
   boot_regular().await;
@@ -210,7 +869,7 @@ async fn test_get_with_content_length_larger_than_body() {
 async fn test_post() {
   boot_regular().await;
   let request = b"POST /test HTTP/1.1\r\n\r\nmueve tu cuerpo";
-  let expected = b"Method: POST\nUri: /test\nParams: {}\nBody: \"mueve tu cuerpo\"";
+  let expected = b"Method: POST\nUri: /test\nParams: {}\nPath params: {}\nBody: \"mueve tu cuerpo\"";
   tokio::time::sleep(std::time::Duration::from_millis(100)).await;
   run_regular(request, expected).await;
 }
@@ -219,7 +878,7 @@ async fn test_post() {
 async fn test_post_without_content_length_empty_body() {
   boot_regular().await;
   let request = b"POST /test HTTP/1.1\r\n\r\n";
-  let expected = b"Method: POST\nUri: /test\nParams: {}\nBody: \"\"";
+  let expected = b"Method: POST\nUri: /test\nParams: {}\nPath params: {}\nBody: \"\"";
   tokio::time::sleep(std::time::Duration::from_millis(100)).await;
   run_regular(request, expected).await;
 }
@@ -228,7 +887,7 @@ async fn test_post_without_content_length_empty_body() {
 async fn test_post_with_query() {
   boot_regular().await;
   let request = b"POST /test?foo=bar HTTP/1.1\r\n\r\nmueve tu cuerpo";
-  let expected = b"Method: POST\nUri: /test\nParams: {\"foo\": \"bar\"}\nBody: \"mueve tu cuerpo\"";
+  let expected = b"Method: POST\nUri: /test\nParams: {\"foo\": \"bar\"}\nPath params: {}\nBody: \"mueve tu cuerpo\"";
   tokio::time::sleep(std::time::Duration::from_millis(100)).await;
   run_regular(request, expected).await;
 }
@@ -237,7 +896,7 @@ async fn test_post_with_query() {
 async fn test_post_with_content_length() {
   boot_regular().await;
   let request = b"POST /test HTTP/1.1\r\nContent-Length: 15\r\n\r\nmueve tu cuerpo";
-  let expected = b"Method: POST\nUri: /test\nParams: {}\nBody: \"mueve tu cuerpo\"";
+  let expected = b"Method: POST\nUri: /test\nParams: {}\nPath params: {}\nBody: \"mueve tu cuerpo\"";
   tokio::time::sleep(std::time::Duration::from_millis(100)).await;
   run_regular(request, expected).await;
 }
@@ -247,7 +906,7 @@ async fn test_post_with_params() {
   boot_regular().await;
   let request = b"POST /test/hola/que?param4=hoy&param3=hace HTTP/1.1\r\n\r\nmueve tu cuerpo";
   let expected =
-    b"Method: POST\nUri: /test/hola/que\nParams: {\"param1\": \"hola\", \"param2\": \"que\", \"param3\": \"hace\", \"param4\": \"hoy\"}\nBody: \"mueve tu cuerpo\"";
+    b"Method: POST\nUri: /test/hola/que\nParams: {\"param3\": \"hace\", \"param4\": \"hoy\"}\nPath params: {\"param1\": \"hola\", \"param2\": \"que\"}\nBody: \"mueve tu cuerpo\"";
   tokio::time::sleep(std::time::Duration::from_millis(100)).await;
   run_regular(request, expected).await;
 }
@@ -256,7 +915,7 @@ async fn test_post_with_params() {
 async fn test_post_with_incomplete_path_params() {
   boot_regular().await;
   let request = b"POST /test/hola HTTP/1.1\r\n\r\nmueve tu cuerpo";
-  let expected = b"Method: POST\nUri: /test/hola\nParams: {\"param1\": \"hola\"}\nBody: \"mueve tu cuerpo\"";
+  let expected = b"Method: POST\nUri: /test/hola\nParams: {}\nPath params: {\"param1\": \"hola\"}\nBody: \"mueve tu cuerpo\"";
   tokio::time::sleep(std::time::Duration::from_millis(100)).await;
   run_regular(request, expected).await;
 }
@@ -265,7 +924,7 @@ async fn test_post_with_incomplete_path_params() {
 async fn test_post_without_content_length_body() {
   boot_regular().await;
   let request = b"POST /test HTTP/1.1\r\n\r\nbody";
-  let expected = b"Method: POST\nUri: /test\nParams: {}\nBody: \"body\"";
+  let expected = b"Method: POST\nUri: /test\nParams: {}\nPath params: {}\nBody: \"body\"";
   tokio::time::sleep(std::time::Duration::from_millis(100)).await;
   run_regular(request, expected).await;
 }
@@ -274,7 +933,7 @@ async fn test_post_without_content_length_body() {
 async fn test_post_with_matching_content_length() {
   boot_regular().await;
   let request = b"POST /test HTTP/1.1\r\nContent-Length: 4\r\n\r\nbody";
-  let expected = b"Method: POST\nUri: /test\nParams: {}\nBody: \"body\"";
+  let expected = b"Method: POST\nUri: /test\nParams: {}\nPath params: {}\nBody: \"body\"";
   tokio::time::sleep(std::time::Duration::from_millis(100)).await;
   run_regular(request, expected).await;
 }
@@ -283,7 +942,7 @@ async fn test_post_with_matching_content_length() {
 async fn test_post_with_smaller_content_length() {
   boot_regular().await;
   let request = b"POST /test HTTP/1.1\r\nContent-Length: 2\r\n\r\nbody";
-  let expected = b"Method: POST\nUri: /test\nParams: {}\nBody: \"bo\"";
+  let expected = b"Method: POST\nUri: /test\nParams: {}\nPath params: {}\nBody: \"bo\"";
   tokio::time::sleep(std::time::Duration::from_millis(100)).await;
   run_regular(request, expected).await;
 }
@@ -355,7 +1014,7 @@ async fn test_patch() {
 async fn test_head() {
   boot_regular().await;
   let request = b"HEAD /test HTTP/1.1\r\n\r\n";
-  let expected = b"head";
+  let expected = b"Content-Length: 4\r\n";
   tokio::time::sleep(std::time::Duration::from_millis(100)).await;
   run_regular(request, expected).await;
 }
@@ -436,7 +1095,7 @@ async fn test_delete_with_content_length_larger_than_body() {
 async fn test_strict_mode_without_content_length() {
   boot_strict().await;
   let request = b"POST /test HTTP/1.1\r\n\r\npayload";
-  let expected = b"Method: POST\nUri: /test\nParams: {}\nBody: \"payload\"";
+  let expected = b"Method: POST\nUri: /test\nParams: {}\nPath params: {}\nBody: \"payload\"";
   tokio::time::sleep(std::time::Duration::from_millis(100)).await;
   run_strict(request, expected).await;
 }
@@ -445,7 +1104,7 @@ async fn test_strict_mode_without_content_length() {
 async fn test_strict_mode_with_content_length() {
   boot_strict().await;
   let request = b"POST /test HTTP/1.1\r\nContent-Length: 7\r\n\r\npayload";
-  let expected = b"Method: POST\nUri: /test\nParams: {}\nBody: \"payload\"";
+  let expected = b"Method: POST\nUri: /test\nParams: {}\nPath params: {}\nBody: \"payload\"";
   tokio::time::sleep(std::time::Duration::from_millis(100)).await;
   run_strict(request, expected).await;
 }
@@ -539,3 +1198,1232 @@ async fn test_missing_method() {
   tokio::time::sleep(std::time::Duration::from_millis(100)).await;
   run_regular(request, expected).await;
 }
+
+#[tokio::test]
+async fn test_post_binary_body() {
+  boot_regular().await;
+  let request = b"POST /binary HTTP/1.1\r\nContent-Length: 6\r\n\r\n\x89PNG\x00\xff";
+  let expected = b"Bytes: [137, 80, 78, 71, 0, 255]";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[cfg(feature = "json")]
+#[tokio::test]
+async fn test_post_json_body() {
+  boot_regular().await;
+  let request = b"POST /json HTTP/1.1\r\nContent-Length: 14\r\n\r\n{\"name\":\"Ana\"}";
+  let expected = b"Hello, Ana";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[cfg(feature = "json")]
+#[tokio::test]
+async fn test_post_invalid_json_body() {
+  boot_regular().await;
+  let request = b"POST /json HTTP/1.1\r\nContent-Length: 5\r\n\r\n{nope";
+  let expected = b"HTTP/1.1 400 Bad Request";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_binary_files_for_every_mime_type() {
+  boot_regular().await;
+  let payload = binary_payload();
+  for (ext, mime) in MIME_TYPES {
+    let request = format!("GET /sample.{} HTTP/1.1\r\n\r\n", ext);
+    let response = fetch_raw(request.as_bytes()).await;
+    let split = response
+      .windows(4)
+      .position(|w| w == b"\r\n\r\n")
+      .expect("response head");
+    let head = String::from_utf8_lossy(&response[..split]);
+    let body = &response[split + 4..];
+    assert!(
+      head.contains(&format!("Content-Type: {}\r\n", mime)),
+      "wrong content type for .{}: {}",
+      ext,
+      head
+    );
+    assert!(
+      head.contains(&format!("Content-Length: {}\r\n", payload.len())),
+      "wrong content length for .{}: {}",
+      ext,
+      head
+    );
+    assert_eq!(body, payload.as_slice(), "body mangled for .{}", ext);
+  }
+}
+
+#[tokio::test]
+async fn test_post_chunked_body() {
+  boot_regular().await;
+  let request = b"POST /test HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nWiki\r\n5;note=x\r\npedia\r\n0\r\nX-Checksum: 1\r\n\r\n";
+  let expected = b"Method: POST\nUri: /test\nParams: {}\nPath params: {}\nBody: \"Wikipedia\"";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_post_large_chunked_body() {
+  boot_regular().await;
+  let payload = "0123456789".repeat(300);
+  let mut request = b"POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+  for piece in payload.as_bytes().chunks(700) {
+    request.extend_from_slice(format!("{:x}\r\n", piece.len()).as_bytes());
+    request.extend_from_slice(piece);
+    request.extend_from_slice(b"\r\n");
+  }
+  request.extend_from_slice(b"0\r\n\r\n");
+  let request = &request;
+  let expected = payload.as_bytes();
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_post_invalid_chunk_size() {
+  boot_regular().await;
+  let request = b"POST /test HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\nhello\r\n0\r\n\r\n";
+  let expected = b"HTTP/1.1 400 Bad Request";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_streamed_iterator_body() {
+  boot_regular().await;
+  let request = b"GET /report.csv HTTP/1.1\r\n\r\n";
+  let expected = b"\r\n\r\n5\r\nrow0\n\r\n5\r\nrow1\n\r\n5\r\nrow2\n\r\n0\r\n\r\n";
+  let response = run_regular(request, expected).await;
+  assert!(response.contains("Transfer-Encoding: chunked\r\n"));
+  assert!(!response.contains("Content-Length"));
+}
+
+#[tokio::test]
+async fn test_streamed_async_body() {
+  boot_regular().await;
+  let request = b"GET /report-stream.csv HTTP/1.1\r\n\r\n";
+  let expected = b"\r\n\r\n5\r\nrow0\n\r\n5\r\nrow1\n\r\n5\r\nrow2\n\r\n0\r\n\r\n";
+  let response = run_regular(request, expected).await;
+  assert!(response.contains("Transfer-Encoding: chunked\r\n"));
+  assert!(!response.contains("Content-Length"));
+}
+
+#[tokio::test]
+async fn test_keep_alive_serves_sequential_requests() {
+  boot_keep_alive().await;
+  let mut stream = open_keep_alive();
+  let first = exchange(&mut stream, b"GET /test HTTP/1.1\r\n\r\n");
+  assert!(first.ends_with("get"), "{}", first);
+  assert!(!first.contains("Connection: close"), "{}", first);
+  let second = exchange(&mut stream, b"POST /test HTTP/1.1\r\nContent-Length: 4\r\n\r\nping");
+  assert!(second.contains("Body: \"ping\""), "{}", second);
+  assert!(!second.contains("Connection: close"), "{}", second);
+}
+
+#[tokio::test]
+async fn test_keep_alive_honors_connection_close() {
+  boot_keep_alive().await;
+  let mut stream = open_keep_alive();
+  let response = exchange(&mut stream, b"GET /test HTTP/1.1\r\nConnection: close\r\n\r\n");
+  assert!(response.contains("Connection: close\r\n"), "{}", response);
+  assert!(is_closed(&mut stream));
+}
+
+#[tokio::test]
+async fn test_keep_alive_max_requests_per_connection() {
+  boot_keep_alive().await;
+  let mut stream = open_keep_alive();
+  for _ in 0..2 {
+    let response = exchange(&mut stream, b"GET /test HTTP/1.1\r\n\r\n");
+    assert!(!response.contains("Connection: close"), "{}", response);
+  }
+  let last = exchange(&mut stream, b"GET /test HTTP/1.1\r\n\r\n");
+  assert!(last.contains("Connection: close\r\n"), "{}", last);
+  assert!(is_closed(&mut stream));
+}
+
+#[tokio::test]
+async fn test_keep_alive_idle_timeout() {
+  boot_keep_alive().await;
+  let mut stream = open_keep_alive();
+  let response = exchange(&mut stream, b"GET /test HTTP/1.1\r\n\r\n");
+  assert!(!response.contains("Connection: close"), "{}", response);
+  tokio::time::sleep(std::time::Duration::from_millis(600)).await;
+  assert!(is_closed(&mut stream));
+}
+
+#[tokio::test]
+async fn test_pipelined_requests_answered_in_order() {
+  boot_keep_alive().await;
+  let request = b"GET /test HTTP/1.1\r\n\r\nPOST /test HTTP/1.1\r\nContent-Length: 4\r\n\r\npingGET / HTTP/1.1\r\n\r\n";
+  let response = run_keep_alive(request, b"home").await;
+  assert_eq!(response.matches("HTTP/1.1 200 OK").count(), 3, "{}", response);
+  let get = response.find("\r\n\r\nget").expect("GET response");
+  let post = response.find("Body: \"ping\"").expect("POST response");
+  let home = response.find("\r\n\r\nhome").expect("home response");
+  assert!(get < post && post < home, "{}", response);
+}
+
+#[tokio::test]
+async fn test_pipelined_after_chunked_body() {
+  boot_keep_alive().await;
+  let request = b"POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\nGET /test HTTP/1.1\r\n\r\n";
+  let response = run_keep_alive(request, b"get").await;
+  let echo = response.find("\r\n\r\nhello").expect("echo response");
+  let get = response.find("\r\n\r\nget").expect("GET response");
+  assert!(echo < get, "{}", response);
+}
+
+#[tokio::test]
+async fn test_pipelined_stops_after_connection_close() {
+  boot_keep_alive().await;
+  let request = b"GET /test HTTP/1.1\r\nConnection: close\r\n\r\nGET / HTTP/1.1\r\n\r\n";
+  let response = run_keep_alive(request, b"get").await;
+  assert_eq!(response.matches("HTTP/1.1 ").count(), 1, "{}", response);
+  assert!(!response.contains("home"), "{}", response);
+}
+
+#[tokio::test]
+async fn test_limits_allow_requests_within_bounds() {
+  boot_limited().await;
+  let request = b"GET /test HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\nD: 4\r\n\r\n";
+  let expected = b"get";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_limited(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_limits_too_many_headers() {
+  boot_limited().await;
+  let request = b"GET /test HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\nD: 4\r\nE: 5\r\n\r\n";
+  let expected = b"HTTP/1.1 431 Request Header Fields Too Large";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_limited(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_limits_uri_too_long() {
+  boot_limited().await;
+  let request = b"GET /test/abcdefghijklmnopqrstuvwxyz0123456789 HTTP/1.1\r\n\r\n";
+  let expected = b"HTTP/1.1 414 URI Too Long";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_limited(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_limits_body_too_large() {
+  boot_limited().await;
+  let request = b"POST /echo HTTP/1.1\r\nContent-Length: 17\r\n\r\n01234567890123456";
+  let expected = b"HTTP/1.1 413 Payload Too Large";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_limited(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_limits_chunked_body_too_large() {
+  boot_limited().await;
+  let request = b"POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n9\r\n012345678\r\n9\r\n012345678\r\n0\r\n\r\n";
+  let expected = b"HTTP/1.1 413 Payload Too Large";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_limited(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_limits_unframed_body_too_large() {
+  boot_limited().await;
+  let request = b"POST /echo HTTP/1.1\r\n\r\n01234567890123456";
+  let expected = b"HTTP/1.1 413 Payload Too Large";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_limited(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_limits_header_bytes() {
+  boot_limited().await;
+  let request = format!("GET /test HTTP/1.1\r\nX-Big: {}\r\n\r\n", "x".repeat(600));
+  let expected = b"HTTP/1.1 431 Request Header Fields Too Large";
+  run_limited(request.as_bytes(), expected).await;
+}
+
+#[tokio::test]
+async fn test_limits_request_line_over_header_budget() {
+  boot_limited().await;
+  let request = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(600));
+  let expected = b"HTTP/1.1 414 URI Too Long";
+  run_limited(request.as_bytes(), expected).await;
+}
+
+#[tokio::test]
+async fn test_limits_header_read_timeout() {
+  boot_limited().await;
+  let response = fetch_stalled(b"GET /test HTTP/1.1\r\nHost: localhost\r\n");
+  assert!(response.starts_with("HTTP/1.1 408 Request Timeout"), "{}", response);
+}
+
+#[tokio::test]
+async fn test_limits_body_read_timeout() {
+  boot_limited().await;
+  let response = fetch_stalled(b"POST /echo HTTP/1.1\r\nContent-Length: 10\r\n\r\nabc");
+  assert!(response.starts_with("HTTP/1.1 408 Request Timeout"), "{}", response);
+}
+
+#[tokio::test]
+async fn test_huge_content_length_rejected_before_reading() {
+  boot_regular().await;
+  let request = b"POST /echo HTTP/1.1\r\nContent-Length: 99999999999\r\n\r\nabc";
+  let expected = b"HTTP/1.1 413 Payload Too Large";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_overflowing_content_length_rejected() {
+  boot_regular().await;
+  let request = b"POST /echo HTTP/1.1\r\nContent-Length: 999999999999999999999999\r\n\r\nabc";
+  let expected = b"HTTP/1.1 413 Payload Too Large";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_invalid_content_length_rejected() {
+  boot_regular().await;
+  let request = b"POST /echo HTTP/1.1\r\nContent-Length: -1\r\n\r\nabc";
+  let expected = b"HTTP/1.1 400 Bad Request";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_duplicate_content_length_rejected() {
+  boot_regular().await;
+  let request = b"POST /echo HTTP/1.1\r\nContent-Length: 3\r\nContent-Length: 3\r\n\r\nabc";
+  let expected = b"HTTP/1.1 400 Bad Request";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_conflicting_content_length_rejected() {
+  boot_regular().await;
+  let request = b"POST /echo HTTP/1.1\r\nContent-Length: 3\r\nContent-Length: 5\r\n\r\nabcde";
+  let expected = b"HTTP/1.1 400 Bad Request";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_content_length_with_transfer_encoding_rejected() {
+  boot_regular().await;
+  let request = b"POST /echo HTTP/1.1\r\nContent-Length: 3\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n";
+  let expected = b"HTTP/1.1 400 Bad Request";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_unframed_transfer_encoding_rejected() {
+  boot_regular().await;
+  let request = b"POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked, gzip\r\n\r\nabc";
+  let expected = b"HTTP/1.1 400 Bad Request";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_builder_response_headers() {
+  boot_regular().await;
+  let response = String::from_utf8(fetch_raw(b"GET /created HTTP/1.1\r\n\r\n").await).unwrap();
+  assert!(response.starts_with("HTTP/1.1 201 Created\r\n"), "{}", response);
+  assert!(response.contains("Content-Type: text/plain; charset=utf-8\r\n"), "{}", response);
+  assert!(response.contains("Location: /test/42\r\n"), "{}", response);
+  assert!(response.contains("Set-Cookie: a=1\r\nSet-Cookie: b=2\r\n"), "{}", response);
+  assert!(!response.contains("Evil"), "{}", response);
+  assert!(response.ends_with("\r\n\r\ncreated"), "{}", response);
+}
+
+#[cfg(feature = "json")]
+#[tokio::test]
+async fn test_builder_json_response() {
+  boot_regular().await;
+  let response = String::from_utf8(fetch_raw(b"GET /created-json HTTP/1.1\r\n\r\n").await).unwrap();
+  assert!(response.starts_with("HTTP/1.1 201 Created\r\n"), "{}", response);
+  assert!(response.contains("Content-Type: application/json\r\n"), "{}", response);
+  assert!(response.contains("Cache-Control: no-store\r\n"), "{}", response);
+  assert!(response.ends_with("\r\n\r\n{\"id\":42}"), "{}", response);
+}
+
+#[tokio::test]
+async fn test_custom_status_code() {
+  boot_regular().await;
+  let request = b"GET /custom-status HTTP/1.1\r\n\r\n";
+  let expected = b"HTTP/1.1 599 Network Read Timeout\r\n";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_router_static_beats_param() {
+  boot_regular().await;
+  let request = b"GET /items/special HTTP/1.1\r\n\r\n";
+  let expected = b"\r\n\r\nspecial item";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_router_param_segment() {
+  boot_regular().await;
+  let request = b"GET /items/42 HTTP/1.1\r\n\r\n";
+  let expected = b"\r\n\r\nitem 42";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_router_catch_all_takes_remaining_segments() {
+  boot_regular().await;
+  let request = b"GET /items/42/extra/parts HTTP/1.1\r\n\r\n";
+  let expected = b"\r\n\r\nrest 42/extra/parts";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_router_backtracks_from_static_branch() {
+  boot_regular().await;
+  let request = b"GET /items/special/info HTTP/1.1\r\n\r\n";
+  let expected = b"\r\n\r\nrest special/info";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_query_does_not_shadow_path_param() {
+  boot_regular().await;
+  let request = b"GET /items/42?id=7 HTTP/1.1\r\n\r\n";
+  let expected = b"\r\n\r\nitem 42";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_path_and_query_params_are_kept_apart() {
+  boot_regular().await;
+  let request = b"POST /test/hola/que?param1=query HTTP/1.1\r\n\r\n";
+  let expected = b"Method: POST\nUri: /test/hola/que\nParams: {\"param1\": \"query\"}\nPath params: {\"param1\": \"hola\", \"param2\": \"que\"}\nBody: \"\"";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_router_catch_all_mounts_assets() {
+  boot_regular().await;
+  let request = b"GET /assets/css/site/main.css HTTP/1.1\r\n\r\n";
+  let expected = b"\r\n\r\nasset css/site/main.css";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_router_mixed_segment_splits_name_and_extension() {
+  boot_regular().await;
+  let request = b"GET /files/archive.tar.gz HTTP/1.1\r\n\r\n";
+  let expected = b"\r\n\r\nfile archive.tar gz";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_router_mixed_segment_needs_its_literal() {
+  boot_regular().await;
+  let request = b"GET /files/README HTTP/1.1\r\n\r\n";
+  let expected = b"HTTP/1.1 404 Not Found";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_router_mixed_segment_beats_param() {
+  boot_regular().await;
+  let request = b"GET /v1/users/42.json HTTP/1.1\r\n\r\n";
+  let expected = b"\r\n\r\nuser json 42";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_router_param_when_mixed_segment_misses() {
+  boot_regular().await;
+  let request = b"GET /v1/users/42 HTTP/1.1\r\n\r\n";
+  let expected = b"\r\n\r\nuser 42";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_router_optional_trailing_segment_present() {
+  boot_regular().await;
+  let request = b"GET /posts/3 HTTP/1.1\r\n\r\n";
+  let expected = b"\r\n\r\nposts page 3";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_router_optional_trailing_segment_absent() {
+  boot_regular().await;
+  let request = b"GET /posts HTTP/1.1\r\n\r\n";
+  let expected = b"\r\n\r\nposts page none";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_router_constraint_integer() {
+  boot_regular().await;
+  let request = b"GET /orders/41 HTTP/1.1\r\n\r\n";
+  let expected = b"\r\n\r\norder 42";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_router_constraint_pattern() {
+  boot_regular().await;
+  let request = b"GET /orders/summer-sale HTTP/1.1\r\n\r\n";
+  let expected = b"\r\n\r\norder slug summer-sale";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_router_constraint_uuid() {
+  boot_regular().await;
+  let request = b"GET /orders/67e55044-10b1-426f-9247-bb680e5fe0c8 HTTP/1.1\r\n\r\n";
+  let expected = b"\r\n\r\norder uuid 67e55044-10b1-426f-9247-bb680e5fe0c8";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_router_constraint_mismatch_is_not_found() {
+  boot_regular().await;
+  let request = b"GET /orders/Summer_Sale HTTP/1.1\r\n\r\n";
+  let expected = b"HTTP/1.1 404 Not Found";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_router_constraint_in_mixed_segment() {
+  boot_regular().await;
+  let request = b"GET /reports/2024.csv HTTP/1.1\r\n\r\n";
+  let expected = b"\r\n\r\nreport year 2024";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_typed_param_parse_error_is_bad_request() {
+  boot_regular().await;
+  let request = b"GET /typed/abc HTTP/1.1\r\n\r\n";
+  let expected = b"HTTP/1.1 400 Bad Request";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_typed_param_parse_error_message() {
+  boot_regular().await;
+  let request = b"GET /typed/abc HTTP/1.1\r\n\r\n";
+  let expected = b"invalid path parameter `id` = \"abc\"";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_typed_param_parses() {
+  boot_regular().await;
+  let request = b"GET /typed/7 HTTP/1.1\r\n\r\n";
+  let expected = b"\r\n\r\ntyped 7";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_known_path_wrong_method_is_method_not_allowed() {
+  boot_regular().await;
+  let request = b"GET /echo HTTP/1.1\r\n\r\n";
+  let expected = b"HTTP/1.1 405 Method Not Allowed";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_method_not_allowed_lists_allowed_methods() {
+  boot_regular().await;
+  let request = b"GET /echo HTTP/1.1\r\n\r\n";
+  let expected = b"Allow: POST, OPTIONS\r\n";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_method_not_allowed_through_param_route() {
+  boot_regular().await;
+  let request = b"DELETE /items/42 HTTP/1.1\r\n\r\n";
+  let expected = b"Allow: GET, HEAD, OPTIONS\r\n";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_options_on_known_path_lists_methods() {
+  boot_regular().await;
+  let request = b"OPTIONS /echo HTTP/1.1\r\n\r\n";
+  let expected = b"HTTP/1.1 204 No Content";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_options_on_known_path_allow_header() {
+  boot_regular().await;
+  let request = b"OPTIONS /test/hola HTTP/1.1\r\n\r\n";
+  let expected = b"Allow: POST, OPTIONS\r\n";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_unknown_path_stays_not_found() {
+  boot_regular().await;
+  let request = b"DELETE /nowhere HTTP/1.1\r\n\r\n";
+  let expected = b"HTTP/1.1 404 Not Found";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_head_explicit_handler_body_is_dropped() {
+  boot_regular().await;
+  let response = String::from_utf8(fetch_raw(b"HEAD /test HTTP/1.1\r\n\r\n").await).unwrap();
+  assert!(response.contains("Content-Length: 4\r\n"), "{}", response);
+  assert!(response.ends_with("\r\n\r\n"), "{}", response);
+}
+
+#[tokio::test]
+async fn test_head_falls_back_to_get_route() {
+  boot_regular().await;
+  let response = String::from_utf8(fetch_raw(b"HEAD /items/42 HTTP/1.1\r\n\r\n").await).unwrap();
+  assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+  assert!(response.contains("Content-Length: 7\r\n"), "{}", response);
+  assert!(response.ends_with("\r\n\r\n"), "{}", response);
+}
+
+#[tokio::test]
+async fn test_head_on_streamed_route_sends_no_chunks() {
+  boot_regular().await;
+  let response = String::from_utf8(fetch_raw(b"HEAD /report.csv HTTP/1.1\r\n\r\n").await).unwrap();
+  assert!(response.contains("Transfer-Encoding: chunked\r\n"), "{}", response);
+  assert!(response.ends_with("\r\n\r\n"), "{}", response);
+}
+
+#[tokio::test]
+async fn test_head_keeps_connection_in_sync() {
+  boot_keep_alive().await;
+  use std::io::{Read, Write};
+  let mut stream = open_keep_alive();
+  stream
+    .write_all(b"HEAD /items/42 HTTP/1.1\r\n\r\nGET /items/42 HTTP/1.1\r\nConnection: close\r\n\r\n")
+    .expect("write requests");
+  let mut raw = Vec::new();
+  let _ = stream.read_to_end(&mut raw);
+  let response = String::from_utf8_lossy(&raw);
+  assert_eq!(response.matches("HTTP/1.1 200 OK").count(), 2, "{}", response);
+  assert!(response.contains("\r\n\r\nHTTP/1.1 200 OK"), "{}", response);
+  assert!(response.ends_with("\r\n\r\nitem 42"), "{}", response);
+}
+
+#[tokio::test]
+async fn test_head_on_unknown_path_is_not_found() {
+  boot_regular().await;
+  let request = b"HEAD /nowhere HTTP/1.1\r\n\r\n";
+  let expected = b"HTTP/1.1 404 Not Found";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_router_group_route() {
+  boot_regular().await;
+  let request = b"GET /api/v1/users HTTP/1.1\r\n\r\n";
+  let expected = b"\r\n\r\ngroup users";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_router_group_param_route() {
+  boot_regular().await;
+  let request = b"GET /api/v1/users/7 HTTP/1.1\r\n\r\n";
+  let expected = b"\r\n\r\ngroup user 7";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_router_group_middleware_applies() {
+  boot_regular().await;
+  let request = b"GET /api/v1/users HTTP/1.1\r\n\r\n";
+  let expected = b"X-Group: v1\r\n";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_router_group_is_only_under_prefix() {
+  boot_regular().await;
+  let request = b"GET /users HTTP/1.1\r\n\r\n";
+  let expected = b"HTTP/1.1 404 Not Found";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_router_group_cors_policy() {
+  boot_regular().await;
+  let request = b"GET /api/v1/users HTTP/1.1\r\nOrigin: http://app.example\r\n\r\n";
+  let expected = b"Access-Control-Allow-Origin: http://app.example\r\n";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_router_nested_group_middleware_short_circuits() {
+  boot_regular().await;
+  let request = b"GET /api/v1/admin/stats HTTP/1.1\r\n\r\n";
+  let expected = b"HTTP/1.1 401 Unauthorized";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_router_group_middleware_stays_in_group() {
+  boot_regular().await;
+  let response = String::from_utf8(fetch_raw(b"GET /test HTTP/1.1\r\n\r\n").await).unwrap();
+  assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+  assert!(!response.contains("X-Group"), "{}", response);
+  assert!(response.contains("Access-Control-Allow-Origin: *\r\n"), "{}", response);
+}
+
+#[tokio::test]
+async fn test_router_group_cors_replaces_server_policy() {
+  boot_regular().await;
+  let request = b"GET /api/v1/users HTTP/1.1\r\nOrigin: http://app.example\r\n\r\n";
+  let response = String::from_utf8(fetch_raw(request).await).unwrap();
+  assert_eq!(response.matches("Access-Control-Allow-Origin").count(), 1, "{}", response);
+}
+
+#[tokio::test]
+async fn test_router_group_cors_answers_preflight() {
+  boot_regular().await;
+  let request = b"OPTIONS /api/v1/users/7 HTTP/1.1\r\nOrigin: http://app.example\r\n\r\n";
+  let response = String::from_utf8(fetch_raw(request).await).unwrap();
+  assert!(response.starts_with("HTTP/1.1 204 No Content\r\n"), "{}", response);
+  assert!(response.contains("Access-Control-Allow-Origin: http://app.example\r\n"), "{}", response);
+  assert!(response.contains("X-Group: v1\r\n"), "{}", response);
+}
+
+#[tokio::test]
+async fn test_router_nested_group_runs_outer_middleware() {
+  boot_regular().await;
+  let request = b"GET /api/v1/admin/stats HTTP/1.1\r\nX-Token: secret\r\n\r\n";
+  let response = String::from_utf8(fetch_raw(request).await).unwrap();
+  assert!(response.ends_with("\r\n\r\nadmin stats"), "{}", response);
+  assert!(response.contains("X-Group: v1\r\n"), "{}", response);
+}
+
+#[tokio::test]
+async fn test_url_for_nested_group_route() {
+  boot_regular().await;
+  let request = b"GET /links HTTP/1.1\r\n\r\n";
+  let expected = b"/api/v1/users/7\n";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_url_for_percent_encodes_values() {
+  boot_regular().await;
+  let request = b"GET /links HTTP/1.1\r\n\r\n";
+  let expected = b"/people/%C3%A4%2Fx%20y\n";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_url_for_catch_all_keeps_slashes() {
+  boot_regular().await;
+  let request = b"GET /links HTTP/1.1\r\n\r\n";
+  let expected = b"/static/css/a%20b.css\n";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_url_for_leaves_out_optional_segment() {
+  boot_regular().await;
+  let request = b"GET /links HTTP/1.1\r\n\r\n";
+  let expected = b"\n/posts\n/posts/2\n";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_url_for_missing_param() {
+  boot_regular().await;
+  let request = b"GET /links HTTP/1.1\r\n\r\n";
+  let expected = b"error: route `person` needs a value for `id`\n";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_url_for_checks_constraints() {
+  boot_regular().await;
+  let request = b"GET /links HTTP/1.1\r\n\r\n";
+  let expected = b"error: route `order` does not accept \"abc\" for `id`\n";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_url_for_unknown_route() {
+  boot_regular().await;
+  let request = b"GET /links HTTP/1.1\r\n\r\n";
+  let expected = b"error: no route named `nowhere`";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_url_for_location_header() {
+  boot_regular().await;
+  let request = b"POST /signup HTTP/1.1\r\n\r\n";
+  let expected = b"Location: /people/42\r\n";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_url_for_location_resolves() {
+  boot_regular().await;
+  let request = b"GET /people/42 HTTP/1.1\r\n\r\n";
+  let expected = b"\r\n\r\nperson 42";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_error_renderer_problem_json_for_unmatched() {
+  boot_branded().await;
+  let request = b"GET /nowhere HTTP/1.1\r\n\r\n";
+  let response = run_branded(request, b"HTTP/1.1 404 Not Found\r\nContent-Type: application/problem+json").await;
+  assert!(response.contains(r#"{"type":"about:blank","title":"Not Found","status":404,"instance":"/nowhere"}"#));
+}
+
+#[tokio::test]
+async fn test_error_renderer_per_status_keeps_allow_header() {
+  boot_branded().await;
+  let request = b"DELETE / HTTP/1.1\r\n\r\n";
+  let response = run_branded(request, b"HTTP/1.1 405 Method Not Allowed\r\nContent-Type: text/html").await;
+  assert!(response.contains("Allow: GET, HEAD, OPTIONS\r\n"));
+  assert!(response.ends_with("<h1>Acme</h1><p>405 Method Not Allowed</p>"));
+}
+
+#[tokio::test]
+async fn test_error_renderer_for_parser_errors() {
+  boot_branded().await;
+  let request = b"GET / HTTP/1.0\r\n\r\n";
+  let expected = br#"{"type":"about:blank","title":"HTTP Version Not Supported","status":505}"#;
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_branded(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_error_renderer_leaves_handler_responses() {
+  boot_branded().await;
+  let request = b"GET /test HTTP/1.1\r\n\r\n";
+  let expected = b"HTTP/1.1 200 OK";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_branded(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_fallback_handler_answers_unmatched() {
+  boot_fallback().await;
+  let request = b"POST /nowhere HTTP/1.1\r\nContent-Length: 0\r\n\r\n";
+  let response = run_fallback(request, b"HTTP/1.1 404 Not Found\r\nContent-Type: text/plain").await;
+  assert!(response.ends_with("nothing at /nowhere"));
+}
+
+#[tokio::test]
+async fn test_fallback_handler_after_static_files() {
+  boot_fallback().await;
+  let request = b"GET /numano.png HTTP/1.1\r\n\r\n";
+  let expected = b"HTTP/1.1 200 OK\r\nContent-Type: image/png";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_fallback(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_route_middleware_short_circuits() {
+  boot_regular().await;
+  let request = b"GET /guarded HTTP/1.1\r\n\r\n";
+  let expected = b"HTTP/1.1 401 Unauthorized";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_route_middleware_passes_through() {
+  boot_regular().await;
+  let request = b"GET /guarded HTTP/1.1\r\nX-Token: secret\r\n\r\n";
+  let expected = b"HTTP/1.1 200 OK";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_global_middleware_wraps_routes() {
+  boot_branded().await;
+  let request = b"GET /test HTTP/1.1\r\n\r\n";
+  let expected = b"X-Group: branded\r\n";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_branded(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_global_middleware_sees_unmatched_requests() {
+  boot_branded().await;
+  let request = b"GET /nowhere HTTP/1.1\r\n\r\n";
+  let response = run_branded(request, b"HTTP/1.1 404 Not Found").await;
+  assert!(response.contains("X-Group: branded\r\n"));
+  assert!(response.contains("Access-Control-Allow-Origin: *\r\n"));
+}
+
+#[tokio::test]
+async fn test_cors_middleware_answers_unmatched_preflight() {
+  boot_regular().await;
+  let request = b"OPTIONS /nowhere HTTP/1.1\r\nOrigin: http://elsewhere.example\r\n\r\n";
+  let response = run_regular(request, b"HTTP/1.1 204 No Content").await;
+  assert!(response.contains("Access-Control-Allow-Origin: *\r\n"));
+  assert!(response.contains("Access-Control-Max-Age: 600\r\n"));
+}
+
+#[tokio::test]
+async fn test_state_reaches_handlers() {
+  boot_regular().await;
+  let request = b"GET /greeting HTTP/1.1\r\n\r\n";
+  let expected = b"\r\n\r\nhello from state; counter: None";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_extensions_pass_between_middleware_and_handler() {
+  boot_regular().await;
+  let request = b"GET /request-id HTTP/1.1\r\n\r\n";
+  let response = run_regular(request, b"X-Served-By: request-id handler\r\n").await;
+  assert!(response.ends_with("\r\n\r\nrequest req-42"));
+}
+
+#[tokio::test]
+async fn test_extract_path_and_state() {
+  boot_regular().await;
+  let request = b"GET /extract/users/42 HTTP/1.1\r\n\r\n";
+  let expected = b"hello from state: user 42";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_extract_path_tuple_in_pattern_order() {
+  boot_regular().await;
+  let request = b"GET /extract/teams/core/members/7 HTTP/1.1\r\n\r\n";
+  let expected = b"core member 7";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_extract_invalid_path_is_bad_request() {
+  boot_regular().await;
+  let request = b"GET /extract/users/abc HTTP/1.1\r\n\r\n";
+  let response = run_regular(request, b"HTTP/1.1 400 Bad Request").await;
+  assert!(response.contains("invalid path parameter `id` = \"abc\""));
+}
+
+#[cfg(feature = "json")]
+#[tokio::test]
+async fn test_extract_query_and_json() {
+  boot_regular().await;
+  let request = b"POST /extract/groups/9/users?notify=true HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: 23\r\n\r\n{\"name\":\"Ana\",\"age\":30}";
+  let expected = b"{\"age\":30,\"group\":9,\"name\":\"Ana\",\"notify\":true}";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[cfg(feature = "json")]
+#[tokio::test]
+async fn test_extract_json_requires_json_content_type() {
+  boot_regular().await;
+  let request = b"POST /extract/groups/9/users HTTP/1.1\r\nContent-Type: text/plain\r\nContent-Length: 23\r\n\r\n{\"name\":\"Ana\",\"age\":30}";
+  run_regular(request, b"HTTP/1.1 415 Unsupported Media Type").await;
+}
+
+#[cfg(feature = "json")]
+#[tokio::test]
+async fn test_extract_malformed_json_is_bad_request() {
+  boot_regular().await;
+  let request = b"POST /extract/groups/9/users HTTP/1.1\r\nContent-Type: application/json; charset=utf-8\r\nContent-Length: 8\r\n\r\n{\"name\":";
+  run_regular(request, b"HTTP/1.1 400 Bad Request").await;
+}
+
+#[cfg(feature = "json")]
+#[tokio::test]
+async fn test_extract_json_of_wrong_shape_is_unprocessable() {
+  boot_regular().await;
+  let request = b"POST /extract/groups/9/users HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: 24\r\n\r\n{\"name\":\"Ana\",\"age\":300}";
+  let response = run_regular(request, b"HTTP/1.1 422 Unprocessable Entity").await;
+  assert!(response.contains("invalid JSON body"));
+}
+
+#[cfg(feature = "json")]
+#[tokio::test]
+async fn test_extract_invalid_query_is_bad_request() {
+  boot_regular().await;
+  let request = b"POST /extract/groups/9/users?notify=maybe HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: 23\r\n\r\n{\"name\":\"Ana\",\"age\":30}";
+  let response = run_regular(request, b"HTTP/1.1 400 Bad Request").await;
+  assert!(response.contains("invalid query string"));
+}
+
+#[tokio::test]
+async fn test_handler_returns_ok_result() {
+  boot_regular().await;
+  let request = b"GET /accounts/1 HTTP/1.1\r\n\r\n";
+  let expected = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 11\r\n";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  let response = run_regular(request, expected).await;
+  assert!(response.ends_with("\r\n\r\naccount ana"));
+}
+
+#[tokio::test]
+async fn test_handler_question_mark_maps_domain_errors() {
+  boot_regular().await;
+  let locked = run_regular(b"GET /accounts/2 HTTP/1.1\r\n\r\n", b"HTTP/1.1 403 Forbidden").await;
+  assert!(locked.ends_with("account locked"));
+  let missing = run_regular(b"GET /accounts/9 HTTP/1.1\r\n\r\n", b"HTTP/1.1 404 Not Found").await;
+  assert!(missing.ends_with("no such account"));
+  let bad = run_regular(b"GET /accounts/abc HTTP/1.1\r\n\r\n", b"HTTP/1.1 400 Bad Request").await;
+  assert!(bad.contains("invalid path parameter `id`"));
+}
+
+#[tokio::test]
+async fn test_handler_returns_option() {
+  boot_regular().await;
+  let found = run_regular(b"GET /nicknames/1 HTTP/1.1\r\n\r\n", b"HTTP/1.1 200 OK").await;
+  assert!(found.ends_with("nana"));
+  run_regular(b"GET /nicknames/2 HTTP/1.1\r\n\r\n", b"HTTP/1.1 404 Not Found").await;
+}
+
+#[tokio::test]
+async fn test_handler_returns_status_and_bytes() {
+  boot_regular().await;
+  let request = b"POST /queued HTTP/1.1\r\nContent-Length: 3\r\n\r\njob";
+  let expected = b"HTTP/1.1 202 Accepted\r\nContent-Type: application/octet-stream\r\nContent-Length: 3\r\n";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  let response = run_regular(request, expected).await;
+  assert!(response.ends_with("\r\n\r\njob"));
+}
+
+#[tokio::test]
+async fn test_handler_returns_bare_status() {
+  boot_regular().await;
+  let request = b"GET /ping HTTP/1.1\r\n\r\n";
+  let expected = b"HTTP/1.1 204 No Content\r\n";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_router_group_cors_refused_origin_gets_no_server_headers() {
+  boot_regular().await;
+  let request = b"GET /partners/users HTTP/1.1\r\nOrigin: https://evil.example\r\n\r\n";
+  let response = String::from_utf8(fetch_raw(request).await).unwrap();
+  assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+  assert!(!response.contains("Access-Control-"), "{}", response);
+  let request = b"GET /partners/users HTTP/1.1\r\nOrigin: https://app.example\r\n\r\n";
+  let response = String::from_utf8(fetch_raw(request).await).unwrap();
+  assert!(response.contains("Access-Control-Allow-Origin: https://app.example\r\n"), "{}", response);
+}
+
+#[tokio::test]
+async fn test_chunked_trailers_stay_out_of_headers() {
+  boot_regular().await;
+  let request = b"POST /trailers HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nok\r\n0\r\nX-Checksum: 1\r\nAuthorization: Bearer evil\r\n\r\n";
+  let expected = b"authorization header: false; trailers: X-Checksum=1,Authorization=Bearer evil";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_limits_too_many_trailer_fields() {
+  boot_limited().await;
+  let request = b"POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nok\r\n0\r\nA: 1\r\nB: 2\r\nC: 3\r\nD: 4\r\nE: 5\r\n\r\n";
+  let expected = b"HTTP/1.1 431 Request Header Fields Too Large";
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_limited(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_limits_trailer_bytes() {
+  boot_limited().await;
+  let mut request = b"POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nok\r\n0\r\n".to_vec();
+  request.extend_from_slice(format!("X-Padding: {}\r\n\r\n", "a".repeat(600)).as_bytes());
+  run_limited(&request, b"HTTP/1.1 431 Request Header Fields Too Large").await;
+}
+
+#[tokio::test]
+async fn test_limits_refusal_keeps_cors_headers() {
+  boot_limited().await;
+  let request = b"POST /echo HTTP/1.1\r\nOrigin: http://app.example\r\nContent-Length: 17\r\n\r\n01234567890123456";
+  let response = run_limited(request, b"HTTP/1.1 413 Payload Too Large").await;
+  assert!(response.contains("Access-Control-Allow-Origin: *\r\n"));
+}
+
+#[tokio::test]
+async fn test_url_for_encoded_value_resolves() {
+  boot_regular().await;
+  let request = b"GET /people/Jos%C3%A9%20%C3%9C HTTP/1.1\r\n\r\n";
+  let expected = "\r\n\r\nperson José Ü".as_bytes();
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_post_with_encoded_params() {
+  boot_regular().await;
+  let request = b"POST /test?q=hello%20world+x&na%6De=Jos%C3%A9&sum=1%2B1 HTTP/1.1\r\n\r\n";
+  let expected = "Method: POST\nUri: /test\nParams: {\"name\": \"José\", \"q\": \"hello world x\", \"sum\": \"1+1\"}\nPath params: {}\nBody: \"\"".as_bytes();
+  tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+  run_regular(request, expected).await;
+}
+
+#[tokio::test]
+async fn test_invalid_custom_status_becomes_500() {
+  boot_regular().await;
+  let expected = b"HTTP/1.1 500 Internal Server Error\r\n";
+  let response = run_regular(b"GET /invalid-status/large HTTP/1.1\r\n\r\n", expected).await;
+  assert!(!response.contains("1000"));
+  let response = run_regular(b"GET /invalid-status/split HTTP/1.1\r\n\r\n", expected).await;
+  assert!(!response.contains("Set-Cookie"));
+}
+
+#[tokio::test]
+async fn test_content_type_with_line_break_is_dropped() {
+  boot_regular().await;
+  let request = b"GET /split-content-type HTTP/1.1\r\n\r\n";
+  let response = run_regular(request, b"HTTP/1.1 200 OK\r\n").await;
+  assert!(!response.contains("Set-Cookie"));
+  assert!(response.ends_with("\r\n\r\nsplit"));
+}
+
+#[tokio::test]
+async fn test_server_new_refuses_invalid_route_list() {
+  let routes = std::collections::HashMap::from([(
+    (Rt::GET, "/items/{id:nope}".to_string()),
+    httpageboy::Rh {
+      handler: handler!(demo_handle_home),
+    },
+  )]);
+  let Err(err) = Server::new("127.0.0.1:0", Some(routes)).await else {
+    panic!("an invalid route list is refused");
+  };
+  assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+  assert!(err.to_string().starts_with("invalid route pattern `/items/{id:nope}`"));
+}
+
+#[tokio::test]
+async fn test_cors_with_non_ascii_header_name() {
+  boot_regular().await;
+  let request = b"GET /unicode-header HTTP/1.1\r\nOrigin: http://x.example\r\n\r\n";
+  let response = run_regular(request, b"HTTP/1.1 200 OK\r\n").await;
+  assert!(response.contains("Access-Control-Allow-Origin: *\r\n"));
+  assert!(response.ends_with("\r\n\r\nunicode"));
+}
+
+#[tokio::test]
+async fn test_static_file_beats_route_for_other_method() {
+  boot_regular().await;
+  let response = run_regular(b"GET /uploads/report.txt HTTP/1.1\r\n\r\n", b"HTTP/1.1 200 OK\r\n").await;
+  assert!(response.ends_with("\r\n\r\nreport"));
+  let request = b"GET /uploads/missing.txt HTTP/1.1\r\n\r\n";
+  let response = run_regular(request, b"HTTP/1.1 405 Method Not Allowed\r\n").await;
+  assert!(response.contains("Allow: POST, OPTIONS\r\n"));
+}
+
+#[tokio::test]
+async fn test_zero_idle_timeout_closes_after_first_response() {
+  boot_no_idle().await;
+  let mut stream = std::net::TcpStream::connect(test_server_url(NO_IDLE_SERVER_URL)).expect("connect to test server");
+  stream
+    .set_read_timeout(Some(Duration::from_secs(2)))
+    .expect("set read timeout");
+  let response = exchange(&mut stream, b"GET /test HTTP/1.1\r\n\r\n");
+  assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+  assert!(response.contains("Connection: close\r\n"), "{}", response);
+  assert!(is_closed(&mut stream));
+}
+
+#[tokio::test]
+async fn test_refused_registration_adds_nothing() {
+  let mut server = Server::new("127.0.0.1:0", None)
+    .await
+    .expect("failed to bind test server");
+  server.add_named_route("home", "/", Rt::GET, handler!(demo_handle_home));
+  server.add_route("/api/b", Rt::GET, handler!(demo_handle_home));
+  let api =
+    Router::new()
+      .route("/a", Rt::GET, handler!(demo_handle_home))
+      .route("/b", Rt::GET, handler!(demo_handle_home));
+  let err = server.try_nest("/api", api).unwrap_err();
+  assert!(matches!(err, httpageboy::RouteError::Conflict { .. }));
+  let err = server
+    .try_add_named_route("home", "/other", Rt::GET, handler!(demo_handle_home))
+    .unwrap_err();
+  assert!(matches!(err, httpageboy::RouteError::DuplicateName { .. }));
+  let err = server
+    .try_add_route_with("/items/{id:nope}", Rt::GET, handler!(demo_handle_home), Vec::new())
+    .unwrap_err();
+  assert!(matches!(err, httpageboy::RouteError::InvalidPattern { .. }));
+  let listed: Vec<String> = server.routes().iter().map(|route| route.to_string()).collect();
+  assert_eq!(listed, ["GET / (home)", "GET /api/b"]);
+  assert_eq!(server.url_for("home", &[]).unwrap(), "/");
+}
+
+#[tokio::test]
+async fn test_content_type_in_headers_replaces_content_type() {
+  boot_regular().await;
+  let request = b"GET /pushed-type HTTP/1.1\r\n\r\n";
+  let response = run_regular(request, b"HTTP/1.1 200 OK\r\n").await;
+  assert!(response.contains("Content-Type: text/csv\r\n"), "{}", response);
+  assert!(!response.contains("text/plain"), "{}", response);
+  assert!(response.ends_with("\r\n\r\na,b"));
+}
@@ -0,0 +1,39 @@
+use httpageboy::{Extensions, Response};
+
+#[derive(Debug, PartialEq)]
+struct UserId(u64);
+
+#[derive(Debug, PartialEq)]
+struct Role(&'static str);
+
+#[test]
+fn test_extensions_keep_one_value_per_type() {
+  let mut extensions = Extensions::new();
+  assert!(extensions.is_empty());
+  assert_eq!(extensions.insert(UserId(7)), None);
+  assert_eq!(extensions.insert(Role("admin")), None);
+  assert_eq!(extensions.get::<UserId>(), Some(&UserId(7)));
+  assert_eq!(extensions.get::<Role>(), Some(&Role("admin")));
+  assert_eq!(extensions.get::<String>(), None);
+  assert_eq!(extensions.len(), 2);
+}
+
+#[test]
+fn test_extensions_replace_and_remove() {
+  let mut extensions = Extensions::new();
+  extensions.insert(UserId(7));
+  assert_eq!(extensions.insert(UserId(8)), Some(UserId(7)));
+  if let Some(user) = extensions.get_mut::<UserId>() {
+    user.0 += 1;
+  }
+  assert_eq!(extensions.remove::<UserId>(), Some(UserId(9)));
+  assert_eq!(extensions.remove::<UserId>(), None);
+  assert!(extensions.is_empty());
+}
+
+#[test]
+fn test_extensions_on_built_responses() {
+  let response = Response::builder().extension(Role("cache-hit")).text("ok");
+  assert_eq!(response.extensions.get::<Role>(), Some(&Role("cache-hit")));
+  assert!(Response::new().extensions.is_empty());
+}
@@ -0,0 +1,37 @@
+use httpageboy::{IntoResponse, Response, StatusCode};
+
+#[test]
+fn test_into_response_text_and_bytes() {
+  let text = "hello".into_response();
+  assert_eq!(text.status, StatusCode::Ok);
+  assert_eq!(text.content_type, "text/plain; charset=utf-8");
+  assert_eq!(text.content, b"hello");
+  assert_eq!(String::from("owned").into_response().content, b"owned");
+  let bytes = vec![0u8, 159, 146, 150].into_response();
+  assert_eq!(bytes.content_type, "application/octet-stream");
+  assert_eq!(bytes.content, [0u8, 159, 146, 150]);
+}
+
+#[test]
+fn test_into_response_status_codes() {
+  let empty = StatusCode::NoContent.into_response();
+  assert_eq!(empty.status, StatusCode::NoContent);
+  assert!(empty.content.is_empty());
+  let made = Response::builder().header("Location", "/users/7").text("made");
+  let created = (StatusCode::Created, made).into_response();
+  assert_eq!(created.status, StatusCode::Created);
+  assert_eq!(created.header("Location"), Some("/users/7"));
+  assert_eq!(created.content, b"made");
+}
+
+#[test]
+fn test_into_response_result_and_option() {
+  let ok: Result<&str, (StatusCode, &str)> = Ok("fine");
+  assert_eq!(ok.into_response().status, StatusCode::Ok);
+  let err: Result<&str, (StatusCode, &str)> = Err((StatusCode::Conflict, "taken"));
+  let err = err.into_response();
+  assert_eq!(err.status, StatusCode::Conflict);
+  assert_eq!(err.content, b"taken");
+  assert_eq!(Some("here").into_response().status, StatusCode::Ok);
+  assert_eq!(None::<String>.into_response().status, StatusCode::NotFound);
+}
@@ -0,0 +1,63 @@
+use httpageboy::core::route_constraint::Constraint;
+
+fn constraint(source: &str) -> Constraint {
+  Constraint::parse(source).unwrap()
+}
+
+#[test]
+fn test_route_constraint_integer_types() {
+  assert!(constraint("u64").accepts("42"));
+  assert!(!constraint("u64").accepts("-1"));
+  assert!(!constraint("u8").accepts("256"));
+  assert!(constraint("i32").accepts("-7"));
+  assert!(!constraint("i32").accepts("seven"));
+}
+
+#[test]
+fn test_route_constraint_uuid() {
+  let uuid = constraint("uuid");
+  assert!(uuid.accepts("67e55044-10b1-426f-9247-bb680e5fe0c8"));
+  assert!(uuid.accepts("67E55044-10B1-426F-9247-BB680E5FE0C8"));
+  assert!(!uuid.accepts("67e5504410b1426f9247bb680e5fe0c8"));
+  assert!(!uuid.accepts("67e55044-10b1-426f-9247-bb680e5fe0cz"));
+}
+
+#[test]
+fn test_route_constraint_patterns() {
+  let slug = constraint("[a-z-]+");
+  assert!(slug.accepts("summer-sale"));
+  assert!(!slug.accepts("Summer"));
+  assert!(!slug.accepts(""));
+  let version = constraint(r"v\d+\.?\d*");
+  assert!(version.accepts("v2"));
+  assert!(version.accepts("v2.10"));
+  assert!(!version.accepts("2.10"));
+  assert!(constraint("[^.]+").accepts("report"));
+  assert!(!constraint("[^.]+").accepts("report.csv"));
+}
+
+#[test]
+fn test_route_constraint_rejects_bad_sources() {
+  assert!(Constraint::parse("number").is_err());
+  assert!(Constraint::parse("").is_err());
+  assert!(Constraint::parse("[a-z").is_err());
+  assert!(Constraint::parse("+a").is_err());
+  assert!(Constraint::parse("[z-a]").is_err());
+  assert!(Constraint::parse("a{2").is_err());
+  assert!(Constraint::parse("a{x}").is_err());
+  assert!(Constraint::parse("a{3,2}").is_err());
+  assert!(Constraint::parse("{2}").is_err());
+  assert!(Constraint::parse("a}").is_err());
+}
+
+#[test]
+fn test_route_constraint_repetition_counts() {
+  assert!(constraint("a{2}").accepts("aa"));
+  assert!(!constraint("a{2}").accepts("aaa"));
+  let code = constraint("[A-Z]{2,3}-\\d{2,}");
+  assert!(code.accepts("AB-12"));
+  assert!(code.accepts("ABC-12345"));
+  assert!(!code.accepts("A-12"));
+  assert!(!code.accepts("ABCD-12"));
+  assert!(!code.accepts("AB-1"));
+}
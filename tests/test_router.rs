@@ -0,0 +1,217 @@
+#![cfg(any(
+  feature = "sync",
+  feature = "async_tokio",
+  feature = "async_std",
+  feature = "async_smol"
+))]
+use httpageboy::core::handler::sync_h;
+use httpageboy::core::router::RouteTree;
+use httpageboy::{CorsPolicy, Response, RouteError, RouteInfo, Router, Rh, Rt};
+
+fn ok() -> Rh {
+  Rh {
+    handler: sync_h(|_| Response::new()),
+  }
+}
+
+fn conflict(method: Rt, pattern: &str, existing: &str) -> RouteError {
+  RouteError::Conflict {
+    method,
+    pattern: pattern.to_string(),
+    existing: existing.to_string(),
+  }
+}
+
+#[test]
+fn test_router_rejects_duplicate_routes() {
+  let mut routes = RouteTree::new();
+  routes.try_insert(Rt::GET, "/users", ok()).unwrap();
+  let err = routes.try_insert(Rt::GET, "/users", ok()).unwrap_err();
+  assert_eq!(err, conflict(Rt::GET, "/users", "/users"));
+  assert_eq!(err.to_string(), "duplicate route GET /users");
+  assert!(routes.try_insert(Rt::POST, "/users", ok()).is_ok());
+}
+
+#[test]
+fn test_router_rejects_ambiguous_patterns() {
+  let mut routes = RouteTree::new();
+  routes.try_insert(Rt::GET, "/a/{x}", ok()).unwrap();
+  let err = routes.try_insert(Rt::GET, "/a/{y}", ok()).unwrap_err();
+  assert_eq!(
+    err.to_string(),
+    "route GET /a/{y} conflicts with GET /a/{x}: both match the same paths"
+  );
+  routes.try_insert(Rt::GET, "/files/{*path}", ok()).unwrap();
+  assert!(routes.try_insert(Rt::GET, "/files/{*rest}", ok()).is_err());
+  routes.try_insert(Rt::GET, "/f/{name}.{ext}", ok()).unwrap();
+  assert!(routes.try_insert(Rt::GET, "/f/{stem}.{kind}", ok()).is_err());
+}
+
+#[test]
+fn test_router_allows_distinct_constraints() {
+  let mut routes = RouteTree::new();
+  routes.try_insert(Rt::GET, "/items/{id:u64}", ok()).unwrap();
+  routes.try_insert(Rt::GET, "/items/{slug:alpha}", ok()).unwrap();
+  routes.try_insert(Rt::GET, "/items/{rest}", ok()).unwrap();
+  routes.try_insert(Rt::GET, "/items/new", ok()).unwrap();
+  assert!(routes.try_insert(Rt::GET, "/items/{other:u64}", ok()).is_err());
+}
+
+#[test]
+fn test_router_checks_optional_variants_before_adding() {
+  let mut routes = RouteTree::new();
+  routes.try_insert(Rt::GET, "/posts", ok()).unwrap();
+  let err = routes.try_insert(Rt::GET, "/posts/{page?}", ok()).unwrap_err();
+  assert_eq!(err, conflict(Rt::GET, "/posts/{page?}", "/posts"));
+  assert!(routes.find(&Rt::GET, "/posts/2").is_none());
+  assert_eq!(routes.routes().len(), 1);
+}
+
+#[test]
+fn test_router_rejects_invalid_patterns() {
+  let mut routes = RouteTree::new();
+  let err = routes.try_insert(Rt::GET, "/a/{id:nope}", ok()).unwrap_err();
+  assert!(matches!(err, RouteError::InvalidPattern { .. }));
+  assert_eq!(
+    err.to_string(),
+    "invalid route pattern `/a/{id:nope}`: unknown constraint `nope`"
+  );
+}
+
+#[test]
+#[should_panic(expected = "duplicate route GET /users")]
+fn test_router_insert_panics_on_conflict() {
+  let mut routes = RouteTree::new();
+  routes.insert(Rt::GET, "/users", ok());
+  routes.insert(Rt::GET, "/users", ok());
+}
+
+#[test]
+fn test_router_rejects_duplicate_names() {
+  let noop = || sync_h(|_| Response::new());
+  let mut routes = RouteTree::new();
+  let err = routes
+    .try_mount(
+      "/",
+      Router::new()
+        .named_route("user", "/users/{id}", Rt::GET, noop())
+        .named_route("user", "/people/{id}", Rt::GET, noop()),
+    )
+    .unwrap_err();
+  assert_eq!(err.to_string(), "route name `user` is already used by GET /users/{id}");
+}
+
+#[test]
+fn test_router_failed_mount_adds_nothing() {
+  let noop = || sync_h(|_| Response::new());
+  let mut routes = RouteTree::new();
+  routes.insert(Rt::GET, "/api/b", ok());
+  let err = routes
+    .try_mount(
+      "/api",
+      Router::new()
+        .named_route("a", "/a", Rt::GET, noop())
+        .route("/b", Rt::GET, noop())
+        .cors(CorsPolicy::default()),
+    )
+    .unwrap_err();
+  assert_eq!(err.to_string(), "duplicate route GET /api/b");
+  assert_eq!(routes.routes().len(), 1);
+  assert!(routes.find(&Rt::GET, "/api/a").is_none());
+  assert!(routes.url_for("a", &[]).is_err());
+
+  routes.try_insert_named("b", Rt::GET, "/b", ok(), Vec::new()).unwrap();
+  let err = routes
+    .try_insert_named("b", Rt::GET, "/c", ok(), Vec::new())
+    .unwrap_err();
+  assert_eq!(err.to_string(), "route name `b` is already used by GET /b");
+  assert!(routes.find(&Rt::GET, "/c").is_none());
+}
+
+#[test]
+fn test_router_lists_routes() {
+  let noop = || sync_h(|_| Response::new());
+  let mut routes = RouteTree::new();
+  routes.insert(Rt::GET, "/", ok());
+  routes.mount(
+    "/api",
+    Router::new()
+      .named_route("user", "/users/{id:u64}", Rt::GET, noop())
+      .route("/users", Rt::POST, noop())
+      .cors(CorsPolicy::default()),
+  );
+  let listed: Vec<String> = routes.routes().iter().map(RouteInfo::to_string).collect();
+  assert_eq!(
+    listed,
+    [
+      "GET /",
+      "GET /api/users/{id:u64} (user)",
+      "POST /api/users",
+      "OPTIONS /api/users/{id:u64}",
+      "OPTIONS /api/users",
+    ]
+  );
+  assert_eq!(routes.routes()[1].name.as_deref(), Some("user"));
+}
+
+#[test]
+fn test_router_decodes_what_url_for_encodes() {
+  let noop = || sync_h(|_| Response::new());
+  let mut routes = RouteTree::new();
+  routes.mount(
+    "/",
+    Router::new()
+      .named_route("person", "/people/{id}", Rt::GET, noop())
+      .named_route("file", "/files/{*path}", Rt::GET, noop()),
+  );
+  let path = routes.url_for("person", &[("id", "José Ü")]).unwrap();
+  assert_eq!(path, "/people/Jos%C3%A9%20%C3%9C");
+  let found = routes.find(&Rt::GET, &path).unwrap();
+  assert_eq!(found.params["id"], "José Ü");
+  let path = routes.url_for("file", &[("path", "a b/c%d")]).unwrap();
+  assert_eq!(path, "/files/a%20b/c%25d");
+  assert_eq!(routes.find(&Rt::GET, &path).unwrap().params["path"], "a b/c%d");
+  let found = routes.find(&Rt::GET, "/people/a%2Fb").unwrap();
+  assert_eq!(found.params["id"], "a/b");
+}
+
+#[test]
+fn test_router_builds_from_route_list() {
+  let route = |method: Rt, pattern: &str| ((method, pattern.to_string()), ok());
+  let routes = RouteTree::try_from_routes([route(Rt::GET, "/users"), route(Rt::POST, "/users")]).unwrap();
+  assert!(routes.find(&Rt::POST, "/users").is_some());
+  let err = RouteTree::try_from_routes([route(Rt::GET, "/a/{x}"), route(Rt::GET, "/a/{y}")])
+    .err()
+    .unwrap();
+  assert_eq!(err, conflict(Rt::GET, "/a/{y}", "/a/{x}"));
+}
+
+#[test]
+fn test_router_constraint_with_repetition_count() {
+  let mut routes = RouteTree::new();
+  routes.try_insert(Rt::GET, "/langs/{code:[a-z]{2}}", ok()).unwrap();
+  assert_eq!(routes.find(&Rt::GET, "/langs/en").unwrap().params["code"], "en");
+  assert!(routes.find(&Rt::GET, "/langs/eng").is_none());
+  let err = routes
+    .try_insert(Rt::GET, "/teams/{code:[a-z]{2,x}}", ok())
+    .unwrap_err();
+  assert_eq!(
+    err.to_string(),
+    "invalid route pattern `/teams/{code:[a-z]{2,x}}`: invalid repetition `{2,x}` in pattern"
+  );
+}
+
+#[test]
+fn test_router_rejects_empty_and_repeated_parameter_names() {
+  let mut routes = RouteTree::new();
+  let err = routes.try_insert(Rt::GET, "/q/{}", ok()).unwrap_err();
+  assert_eq!(err.to_string(), "invalid route pattern `/q/{}`: empty parameter name");
+  assert!(routes.try_insert(Rt::GET, "/files/{*}", ok()).is_err());
+  let err = routes.try_insert(Rt::GET, "/x/{a}/{a}", ok()).unwrap_err();
+  assert_eq!(
+    err.to_string(),
+    "invalid route pattern `/x/{a}/{a}`: parameter `a` appears more than once"
+  );
+  assert!(routes.try_insert(Rt::GET, "/x/{a}-{a}", ok()).is_err());
+  assert!(routes.routes().is_empty());
+}
@@ -0,0 +1,53 @@
+use httpageboy::{InvalidStatusCode, StatusCode};
+
+#[test]
+fn test_status_code_numbers_round_trip() {
+  assert_eq!(StatusCode::Ok.as_u16(), 200);
+  assert_eq!(StatusCode::NetworkAuthenticationRequired.as_u16(), 511);
+  assert_eq!(StatusCode::from_u16(404), Ok(StatusCode::NotFound));
+  assert!(matches!(StatusCode::try_from(201), Ok(StatusCode::Created)));
+  assert_eq!(u16::from(StatusCode::ImATeapot), 418);
+}
+
+#[test]
+fn test_status_code_display() {
+  assert_eq!(StatusCode::Ok.to_string(), "200 OK");
+  assert_eq!(StatusCode::NonAuthoritativeInformation.to_string(), "203 Non-Authoritative Information");
+  assert_eq!(StatusCode::Custom(599, "Network Read Timeout").to_string(), "599 Network Read Timeout");
+}
+
+#[test]
+fn test_status_code_unregistered_and_invalid() {
+  let unregistered = StatusCode::from_u16(299).unwrap();
+  assert!(matches!(unregistered, StatusCode::Custom(299, "")));
+  assert_eq!(unregistered.to_string(), "299 ");
+  assert_eq!(StatusCode::from_u16(99), Err(InvalidStatusCode(99)));
+  assert_eq!(StatusCode::try_from(1000), Err(InvalidStatusCode(1000)));
+}
+
+#[test]
+fn test_status_code_compares_by_number() {
+  assert_eq!(StatusCode::Custom(404, "Gone Fishing"), StatusCode::NotFound);
+  assert_eq!(StatusCode::NotFound, 404);
+  assert_ne!(StatusCode::Ok, StatusCode::Created);
+}
+
+#[test]
+fn test_status_code_classes() {
+  assert!(StatusCode::Continue.is_informational());
+  assert!(StatusCode::NoContent.is_success());
+  assert!(StatusCode::Found.is_redirection());
+  assert!(StatusCode::NotFound.is_client_error());
+  assert!(StatusCode::Custom(599, "").is_server_error());
+  assert!(!StatusCode::Ok.is_client_error());
+}
+
+#[test]
+fn test_status_code_validity() {
+  assert!(StatusCode::Ok.is_valid());
+  assert!(StatusCode::Custom(299, "").is_valid());
+  assert!(StatusCode::Custom(599, "Network\tRead Timeout").is_valid());
+  assert!(!StatusCode::Custom(99, "Too Low").is_valid());
+  assert!(!StatusCode::Custom(1000, "Too High").is_valid());
+  assert!(!StatusCode::Custom(299, "Fine\r\nSet-Cookie: session=1").is_valid());
+}